@@ -3,8 +3,8 @@ use hungry::mtproto::{AuthKey, Salt};
 use hungry::tl::mtproto::enums::ServerDhParams;
 use hungry::{Envelope, tl};
 use std::future::poll_fn;
-use std::pin::pin;
 use std::task::Poll;
+use std::time::Duration;
 
 const ADDR: &str = "149.154.167.40:443";
 
@@ -62,7 +62,7 @@ async fn generate_auth_key(
     public_key: hungry::crypto::RsaKey,
     reader: &mut hungry::reader::Reader<ReaderDriver, Transport>,
     writer: &mut hungry::writer::Writer<WriterDriver, Transport>,
-) -> anyhow::Result<(AuthKey, Salt)> {
+) -> anyhow::Result<(AuthKey, Salt, i32)> {
     let mut buffer = BytesMut::with_capacity(1024 * 1024);
 
     let mut plain = Plain {
@@ -137,11 +137,11 @@ async fn generate_auth_key(
         }
     };
 
-    let (auth_key, salt) = set_client_dh_params.dh_gen_ok(dh_gen_ok)?;
+    let (auth_key, salt, time_offset) = set_client_dh_params.dh_gen_ok(dh_gen_ok)?;
 
     println!("DhGenOk");
 
-    Ok((auth_key, salt))
+    Ok((auth_key, salt, time_offset))
 }
 
 async fn async_main() -> anyhow::Result<()> {
@@ -158,21 +158,26 @@ async fn async_main() -> anyhow::Result<()> {
 
     let (mut reader, mut writer) = hungry::init(transport, r, buffer, w);
 
-    let (auth_key, salt) = generate_auth_key(public_key, &mut reader, &mut writer).await?;
+    let (auth_key, salt, time_offset) =
+        generate_auth_key(public_key, &mut reader, &mut writer).await?;
 
     let session_id = rand::random();
 
-    let mut sender = hungry::Sender::new(
+    let config = hungry::SenderConfig {
+        ping_interval: Some(Duration::from_secs(5)),
+        ..hungry::SenderConfig::default()
+    };
+
+    let mut sender = hungry::Sender::with_config(
         reader,
         hungry::writer::QueuedWriter::new(writer),
         auth_key,
         salt,
+        time_offset,
         session_id,
+        config,
     );
 
-    let func = tl::mtproto::funcs::Ping { ping_id: 123 };
-    dbg!(sender.invoke(tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func))));
-
     // let func = tl::api::funcs::InvokeWithLayer {
     //     layer: 214,
     //     query: tl::api::funcs::InitConnection {
@@ -190,31 +195,22 @@ async fn async_main() -> anyhow::Result<()> {
     // };
     // dbg!(sender.invoke(tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func))));
 
-    loop {
-        dbg!(
-            poll_fn(|cx| {
-                let mut result = match sender.poll(cx) {
-                    Poll::Ready(Ok(result)) => result,
-                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
-                    Poll::Pending => return Poll::Pending,
-                };
-
-                // for result in result {
-                //     match result {
-                //         Ok(_) => {}
-                //         Err(err) => todo!(),
-                //     }
-                // }
-
-                // result.next().unwrap().unwrap();
-
-                cx.waker().wake_by_ref();
-
-                Poll::Pending
-            })
-            .await
-        )?;
-    }
+    // Wait for the first keepalive ping to be answered.
+    let rtt = poll_fn(|cx| {
+        loop {
+            match sender.poll(cx) {
+                Poll::Ready(Ok(hungry::SenderEvent::Pong { rtt })) => return Poll::Ready(Ok(rtt)),
+                Poll::Ready(Ok(event)) => {
+                    dbg!(event);
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    })
+    .await?;
+
+    dbg!(rtt);
 
     Ok(())
 }
@@ -50,7 +50,7 @@ pub unsafe fn prepare_bytes_unchecked(mut buf: NonNull<u8>, len: usize) -> usize
             buf.add(len).write(0u8);
         }
 
-        if len & 2 == 0 {
+        if matches!(len & 3, 1 | 2) {
             buf.add((len + 1) & !1).cast().write(0u16);
         }
 
@@ -95,7 +95,7 @@ impl SerializeUnchecked for [u8] {
                 buf.add(self.len()).write(0u8);
             }
 
-            if self.len() & 2 == 0 {
+            if matches!(self.len() & 3, 1 | 2) {
                 buf.add((self.len() + 1) & !1).cast().write(0u16);
             }
 
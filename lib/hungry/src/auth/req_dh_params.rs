@@ -2,6 +2,7 @@ use std::fmt;
 
 use rug::{Integer, integer::Order::MsfBe};
 
+use crate::mtproto::MsgIds;
 use crate::{auth, crypto, tl};
 
 use tl::Int256;
@@ -191,6 +192,7 @@ impl ReqDhParams<'_> {
             dh_prime,
             g_a,
             server_time: answer.server_time,
+            time_offset: MsgIds::time_offset_for(answer.server_time),
         })
     }
 }
@@ -18,6 +18,8 @@ pub struct ServerDhParamsOk {
     pub(crate) dh_prime: Integer,
     pub(crate) g_a: Integer,
     pub(crate) server_time: i32,
+    /// Time offset for the `server_time` when the answer was received.
+    pub(crate) time_offset: i32,
 }
 
 impl ServerDhParamsOk {
@@ -26,6 +28,12 @@ impl ServerDhParamsOk {
         self.server_time
     }
 
+    /// Seconds added to the local time to approximate the server time.
+    #[inline]
+    pub fn time_offset(&self) -> i32 {
+        self.time_offset
+    }
+
     pub fn set_client_dh_params(mut self, b: &[u8; 256], retry_id: i64) -> auth::SetClientDhParams {
         let one = Integer::from(1);
 
@@ -81,6 +89,7 @@ impl ServerDhParamsOk {
             dh_prime: self.dh_prime,
             g_a: self.g_a,
             server_time: self.server_time,
+            time_offset: self.time_offset,
             b,
             func,
         }
@@ -37,6 +37,7 @@ pub struct SetClientDhParams {
     pub(crate) dh_prime: Integer,
     pub(crate) g_a: Integer,
     pub(crate) server_time: i32,
+    pub(crate) time_offset: i32,
     pub(crate) b: Integer,
     pub(crate) func: funcs::SetClientDhParams,
 }
@@ -58,10 +59,14 @@ impl SetClientDhParams {
         &self.func
     }
 
+    /// Returns the auth key with the first salt, and the time offset
+    /// to create the [`Sender`] with.
+    ///
+    /// [`Sender`]: crate::Sender
     pub fn dh_gen_ok(
         self,
         response: types::DhGenOk,
-    ) -> Result<(mtproto::AuthKey, mtproto::Salt), DhGenOkError> {
+    ) -> Result<(mtproto::AuthKey, mtproto::Salt, i32), DhGenOkError> {
         use DhGenOkError::*;
 
         if response.nonce != self.func.nonce {
@@ -89,6 +94,6 @@ impl SetClientDhParams {
         let mut salt = i64::from_le_bytes(self.new_nonce[0..8].try_into().unwrap())
             ^ i64::from_le_bytes(self.func.server_nonce[0..8].try_into().unwrap());
 
-        Ok((auth_key, salt))
+        Ok((auth_key, salt, self.time_offset))
     }
 }
@@ -72,3 +72,92 @@ pub fn aes_ige_encrypt(buffer: &mut [u8], key: &AesIgeKey, iv: &mut AesIgeIv) {
         iv2.copy_from_slice(&next_iv2);
     }
 }
+
+pub type AesCtrKey = [u8; 32];
+pub type AesCtrIv = [u8; 16];
+
+/// AES-256 in counter mode with a 128-bit big endian counter.
+/// The keystream position is kept between calls to [`apply`].
+///
+/// [`apply`]: AesCtr::apply
+pub struct AesCtr {
+    cipher: Aes256,
+    counter: [u8; 16],
+    block: [u8; 16],
+    pos: usize,
+}
+
+impl AesCtr {
+    pub fn new(key: &AesCtrKey, iv: &AesCtrIv) -> Self {
+        Self {
+            cipher: Aes256::new(GenericArray::from_slice(key)),
+            counter: *iv,
+            block: [0; 16],
+            pos: 16,
+        }
+    }
+
+    /// Encrypt or decrypt the buffer in-place.
+    pub fn apply(&mut self, buffer: &mut [u8]) {
+        for byte in buffer {
+            if self.pos == 16 {
+                self.block = self.counter;
+
+                self.cipher
+                    .encrypt_block(GenericArray::from_mut_slice(&mut self.block));
+
+                // counter += 1 (big endian)
+                for i in (0..16).rev() {
+                    self.counter[i] = self.counter[i].wrapping_add(1);
+
+                    if self.counter[i] != 0 {
+                        break;
+                    }
+                }
+
+                self.pos = 0;
+            }
+
+            *byte ^= self.block[self.pos];
+
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NIST SP 800-38A, F.5.5 CTR-AES256.Encrypt
+    const KEY: &str = "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4";
+    const IV: &str = "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff";
+
+    const PLAINTEXT: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+    30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710";
+
+    const CIPHERTEXT: &str = "601ec313775789a5b7a7f504bbf3d228f443e3ca4d62b59aca84e990cacaf5c5\
+    2b0930daa23de94ce87017ba2d84988ddfc9c58db67aada613c2dd08457941a6";
+
+    #[test]
+    fn test_crypto_aes_ctr() {
+        let mut key = [0; 32];
+        hex::decode_to_slice(KEY, &mut key).unwrap();
+
+        let mut iv = [0; 16];
+        hex::decode_to_slice(IV, &mut iv).unwrap();
+
+        let mut buffer = hex::decode(PLAINTEXT).unwrap();
+
+        let mut ctr = AesCtr::new(&key, &iv);
+
+        // Uneven chunks to check that the keystream position is kept.
+        let (a, b) = buffer.split_at_mut(7);
+        let (b, c) = b.split_at_mut(30);
+        ctr.apply(a);
+        ctr.apply(b);
+        ctr.apply(c);
+
+        assert_eq!(buffer, hex::decode(CIPHERTEXT).unwrap());
+    }
+}
@@ -0,0 +1,42 @@
+use sha2::digest::Digest;
+
+/// HMAC-SHA256 over the concatenation of `data` parts.
+pub(crate) fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    const BLOCK_LEN: usize = 64;
+
+    let mut block = [0; BLOCK_LEN];
+
+    if key.len() > BLOCK_LEN {
+        block[..32].copy_from_slice(&sha2::Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = sha2::Sha256::new();
+    inner.update(block.map(|x| x ^ 0x36));
+
+    for part in data {
+        inner.update(part);
+    }
+
+    let mut outer = sha2::Sha256::new();
+    outer.update(block.map(|x| x ^ 0x5c));
+    outer.update(inner.finalize());
+
+    outer.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crypto_hmac_sha256() {
+        // RFC 4231, test case 2.
+        const EXPECTED: &str = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+
+        let hmac = hmac_sha256(b"Jefe", &[b"what do ya want ", b"for nothing?"]);
+
+        assert_eq!(hex::encode(hmac), EXPECTED);
+    }
+}
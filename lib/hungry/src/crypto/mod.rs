@@ -1,12 +1,14 @@
 mod aes;
 mod crc32;
 mod factorize;
+mod hmac;
 mod rsa;
 mod sha;
 
 pub(crate) use crc32::crc32;
 pub(crate) use factorize::factorize;
+pub(crate) use hmac::hmac_sha256;
 pub(crate) use sha::{sha1, sha256};
 
-pub use aes::{AesIgeIv, AesIgeKey, aes_ige_decrypt, aes_ige_encrypt};
+pub use aes::{AesCtr, AesCtrIv, AesCtrKey, AesIgeIv, AesIgeKey, aes_ige_decrypt, aes_ige_encrypt};
 pub use rsa::RsaKey;
@@ -65,6 +65,26 @@ impl<S: EnvelopeSize> Envelope<S> {
         (self.header.as_mut(), self.footer.as_mut())
     }
 
+    /// Split the beginning of the header off, leaving an envelope of the inner size.
+    /// Used by transports wrapping another transport.
+    #[must_use]
+    pub(crate) fn narrow<I: EnvelopeSize>(mut self) -> (BytesMut, Envelope<I>) {
+        assert!(
+            S::HEADER >= I::HEADER && S::FOOTER == I::FOOTER,
+            "inner envelope does not fit"
+        );
+
+        let prefix = self.header.split_to(S::HEADER - I::HEADER);
+
+        let envelope = Envelope {
+            header: self.header,
+            footer: self.footer,
+            _marker: PhantomData,
+        };
+
+        (prefix, envelope)
+    }
+
     /// Get a mutable contiguous slice of all buffers.
     #[must_use]
     pub(crate) fn unsplit_slice_mut(&mut self, buffer: &mut BytesMut) -> &mut [u8] {
@@ -1 +1,95 @@
+use std::io::{self, Read, Write};
+use std::ptr::NonNull;
 
+use bytes::{Bytes, BytesMut};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::tl;
+
+use tl::de::{Buf, Deserialize, Error};
+use tl::ser::SerializeUnchecked;
+use tl::{Identifiable, SerializedLen};
+
+/// Largest unpacked object accepted, to not be tricked into unbounded allocations.
+const MAX_UNPACKED_LEN: usize = 16 * 1024 * 1024;
+
+/// Serialized object compressed with gzip, which may appear in place of any
+/// other object. Both parties may use it to reduce the size of large messages.
+///
+/// ```tl
+/// gzip_packed#3072cfa1 packed_data:bytes = Object;
+/// ```
+///
+/// ---
+/// https://core.telegram.org/mtproto/serialize#gzip-packed
+#[derive(Clone, Debug)]
+pub(crate) struct GzipPacked {
+    pub packed_data: tl::Bytes,
+}
+
+impl Identifiable for GzipPacked {
+    const CONSTRUCTOR_ID: u32 = 0x3072cfa1;
+}
+
+impl SerializedLen for GzipPacked {
+    #[inline]
+    fn serialized_len(&self) -> usize {
+        self.packed_data.serialized_len()
+    }
+}
+
+impl SerializeUnchecked for GzipPacked {
+    #[inline]
+    unsafe fn serialize_unchecked(&self, buf: NonNull<u8>) -> NonNull<u8> {
+        unsafe { self.packed_data.serialize_unchecked(buf) }
+    }
+}
+
+impl Deserialize for GzipPacked {
+    #[inline]
+    fn deserialize(buf: &mut Buf) -> Result<Self, Error> {
+        let packed_data = tl::Bytes::deserialize(buf)?;
+
+        Ok(Self { packed_data })
+    }
+}
+
+impl GzipPacked {
+    /// Returns `true` if the serialized object is `gzip_packed`.
+    #[inline]
+    pub fn is_packed(buf: &[u8]) -> bool {
+        buf.starts_with(&Self::CONSTRUCTOR_ID.to_le_bytes())
+    }
+
+    /// Compress the serialized object.
+    pub fn pack(object: &[u8]) -> Self {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+        // Writing to a vector never fails.
+        encoder.write_all(object).unwrap();
+
+        Self {
+            packed_data: encoder.finish().unwrap(),
+        }
+    }
+
+    /// Decompress the packed object.
+    pub fn unpack(&self) -> io::Result<BytesMut> {
+        let mut decoder =
+            GzDecoder::new(self.packed_data.as_slice()).take(MAX_UNPACKED_LEN as u64 + 1);
+
+        let mut unpacked = Vec::with_capacity(self.packed_data.len() * 4);
+        decoder.read_to_end(&mut unpacked)?;
+
+        if unpacked.len() > MAX_UNPACKED_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "gzip packed object is too large",
+            ));
+        }
+
+        Ok(BytesMut::from(Bytes::from(unpacked)))
+    }
+}
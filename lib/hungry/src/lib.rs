@@ -10,10 +10,12 @@ pub mod crypto;
 pub mod mtproto;
 pub mod pack;
 pub mod plain;
+pub mod proxy;
 pub mod reader;
 pub mod transport;
 pub mod unpack;
 pub mod utils;
+pub mod websocket;
 pub mod writer;
 
 use bytes::BytesMut;
@@ -26,7 +28,10 @@ pub use hungry_tl as tl;
 pub(crate) use envelope::envelopes;
 
 pub use envelope::{Envelope, EnvelopeSize};
-pub use sender::Sender;
+pub use sender::{
+    InvokeConfig, InvokeError, OrderedBatch, Response, Sender, SenderConfig, SenderError,
+    SenderEvent,
+};
 
 pub fn init<T: transport::Transport, R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
     transport: T,
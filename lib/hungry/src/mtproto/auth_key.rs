@@ -98,13 +98,31 @@ impl AuthKey {
     /// https://core.telegram.org/mtproto/description#defining-aes-key-and-initialization-vector
     #[must_use]
     pub fn compute_msg_key(&self, plaintext: &[u8], side: mtproto::Side) -> MsgKey {
+        self.compute_msg_key_and_quick_ack(plaintext, side).0
+    }
+
+    /// Compute [`MsgKey`] along with the quick ack token: the first 32 bits
+    /// of `msg_key_large` with the most significant bit set.
+    ///
+    /// ---
+    /// https://core.telegram.org/mtproto/mtproto-transports#quick-ack
+    #[must_use]
+    pub fn compute_msg_key_and_quick_ack(
+        &self,
+        plaintext: &[u8],
+        side: mtproto::Side,
+    ) -> (MsgKey, u32) {
         let x = side.x();
 
         // * msg_key_large = SHA256(substr(auth_key, 88 + x, 32) + plaintext + random_padding);
         let msg_key_large = crypto::sha256!(&self.data[88 + x..88 + x + 32], plaintext);
 
         // * msg_key = substr(msg_key_large, 8, 16);
-        msg_key_large[8..24].try_into().unwrap()
+        let msg_key = msg_key_large[8..24].try_into().unwrap();
+
+        let quick_ack = u32::from_le_bytes(msg_key_large[0..4].try_into().unwrap()) | 0x80000000;
+
+        (msg_key, quick_ack)
     }
 
     /// Compute [`AesIgeKey`] and [`AesIgeIv`].
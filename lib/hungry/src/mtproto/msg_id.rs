@@ -26,14 +26,24 @@ use std::{fmt, time};
 /// https://core.telegram.org/mtproto/description#message-identifier-msg-id
 pub type MsgId = i64;
 
+/// Seconds the estimated server time may drift before [`MsgIds::correct`] adjusts it.
+/// The server accepts messages created up to 30 seconds in the future.
+const MAX_TIME_DRIFT: i32 = 10;
+
 #[must_use]
 pub struct MsgIds {
     last: i64,
+    /// Seconds to add to the local time to get the server time.
+    time_offset: i32,
 }
 
 impl fmt::Display for MsgIds {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "msg ids [last={}]", self.last)
+        write!(
+            f,
+            "msg ids [last={}, time_offset={}]",
+            self.last, self.time_offset
+        )
     }
 }
 
@@ -41,6 +51,7 @@ impl fmt::Debug for MsgIds {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("MsgIds")
             .field("last", &format_args!("{:#016x}", self.last))
+            .field("time_offset", &self.time_offset)
             .finish()
     }
 }
@@ -48,7 +59,10 @@ impl fmt::Debug for MsgIds {
 impl MsgIds {
     #[inline(always)]
     pub const fn new() -> Self {
-        Self { last: 0 }
+        Self {
+            last: 0,
+            time_offset: 0,
+        }
     }
 
     #[must_use]
@@ -75,12 +89,68 @@ impl MsgIds {
         }
     }
 
+    /// Seconds added to the local time to approximate the server time.
+    #[must_use]
+    #[inline(always)]
+    pub const fn time_offset(&self) -> i32 {
+        self.time_offset
+    }
+
+    /// Identifiers stay monotonic: if the offset decreases, the next
+    /// ones continue from the last generated one until the time catches up.
+    #[inline(always)]
+    pub const fn set_time_offset(&mut self, time_offset: i32) {
+        self.time_offset = time_offset;
+    }
+
+    /// Returns the time offset for the current server time,
+    /// e.g. `server_time` of `server_DH_inner_data`.
+    #[must_use]
+    pub fn time_offset_for(server_time: i32) -> i32 {
+        let local_time = system_time().as_secs() as i64;
+
+        (server_time as i64 - local_time) as i32
+    }
+
+    /// Returns the estimated current server time.
+    #[must_use]
+    pub fn server_time(&self) -> i32 {
+        (system_time().as_secs() as i64 + self.time_offset as i64) as i32
+    }
+
+    /// Set the time offset from the current server time,
+    /// e.g. the upper 32 bits of a server message identifier.
+    pub fn set_server_time(&mut self, server_time: i32) {
+        self.time_offset = Self::time_offset_for(server_time);
+    }
+
+    /// Correct the time offset from the identifier of a message the server
+    /// has just created, if it is more than 10 seconds away from
+    /// the estimated server time, e.g. after the local clock was changed.
+    pub fn correct(&mut self, server_msg_id: MsgId) {
+        let server_time = (server_msg_id >> 32) as i32;
+
+        let drift = Self::time_offset_for(server_time) - self.time_offset;
+
+        if drift.abs() > MAX_TIME_DRIFT {
+            self.set_server_time(server_time);
+        }
+    }
+
     #[must_use]
     pub fn get_using_system_time(&mut self) -> MsgId {
-        let unix_time = time::SystemTime::now()
-            .duration_since(time::UNIX_EPOCH)
-            .expect("system clock time to be after the Unix epoch");
+        let unix_time = system_time();
 
-        self.get(unix_time)
+        let secs = unix_time
+            .as_secs()
+            .saturating_add_signed(self.time_offset as i64);
+
+        self.get(time::Duration::new(secs, unix_time.subsec_nanos()))
     }
 }
+
+fn system_time() -> time::Duration {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .expect("system clock time to be after the Unix epoch")
+}
@@ -21,13 +21,14 @@ pub fn pack_plain(mut envelope: PlainEnvelope, buffer: &mut BytesMut, message_id
     envelope.unsplit(buffer, excess);
 }
 
+/// Pack and encrypt the message in-place. Returns the quick ack token of the message.
 pub fn pack_encrypted(
     mut envelope: EncryptedEnvelope,
     buffer: &mut BytesMut,
     auth_key: &AuthKey,
     message: DecryptedMessage,
     msg: Msg,
-) {
+) -> u32 {
     let excess = envelope.adapt(buffer);
     let (h, f) = envelope.buffers();
 
@@ -63,7 +64,7 @@ pub fn pack_encrypted(
 
     let (h, plaintext) = unsafe { buffer.split_at_mut_unchecked(24) };
 
-    let msg_key = auth_key.compute_msg_key(plaintext, Side::Client);
+    let (msg_key, quick_ack) = auth_key.compute_msg_key_and_quick_ack(plaintext, Side::Client);
 
     h[0..8].copy_from_slice(auth_key.id());
     h[8..24].copy_from_slice(&msg_key);
@@ -71,4 +72,6 @@ pub fn pack_encrypted(
     let (aes_key, mut aes_iv) = auth_key.compute_aes_params(&msg_key, Side::Client);
 
     crate::crypto::aes_ige_encrypt(plaintext, &aes_key, &mut aes_iv);
+
+    quick_ack
 }
@@ -2,7 +2,7 @@ use std::ptr::NonNull;
 
 use bytes::BytesMut;
 
-use crate::mtproto::Msg;
+use crate::mtproto::{Msg, MsgId};
 use crate::tl;
 use crate::utils::BytesMutExt;
 
@@ -65,7 +65,14 @@ impl MsgContainer {
         self.buffer.spare_capacity_len() >= Msg::HEADER_LEN + len
     }
 
-    pub fn push<X: tl::Function>(
+    /// Returns `true` if a message of the length could be pushed into
+    /// the container while empty.
+    #[inline(always)]
+    pub fn fits(&self, len: usize) -> bool {
+        self.buffer.capacity() >= Msg::HEADER_LEN + len
+    }
+
+    pub fn push<X: SerializeUnchecked + Identifiable>(
         &mut self,
         msg: Msg,
         x: tl::CalculatedLen<'_, tl::ConstructorId<X>>,
@@ -88,6 +95,60 @@ impl MsgContainer {
         self.length += 1;
     }
 
+    /// Same as [`push`], but with the message already serialized.
+    ///
+    /// [`push`]: Self::push
+    pub fn push_serialized(&mut self, msg: Msg, body: &[u8]) {
+        assert!(
+            body.len().is_multiple_of(4),
+            "message length is not a multiple of 4"
+        );
+
+        if !self.can_push(body.len()) {
+            panic!("msg container buffer does not have enough capacity");
+        }
+
+        unsafe {
+            let mut buf = NonNull::new_unchecked(self.buffer.as_mut_ptr().add(self.buffer.len()));
+
+            buf = msg.serialize_unchecked(buf);
+            buf = (body.len() as i32).serialize_unchecked(buf);
+            buf.copy_from_nonoverlapping(NonNull::from(body).cast(), body.len());
+
+            self.buffer
+                .set_len(self.buffer.len() + Msg::HEADER_LEN + body.len());
+        }
+
+        self.length += 1;
+    }
+
+    /// Remove the message with the identifier. Returns `false` if not pushed.
+    pub fn remove(&mut self, msg_id: MsgId) -> bool {
+        let mut offset = 0;
+
+        while offset < self.buffer.len() {
+            let header = &self.buffer[offset..offset + Msg::HEADER_LEN];
+
+            let id = i64::from_le_bytes(header[0..8].try_into().unwrap());
+            let len = i32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+
+            let end = offset + Msg::HEADER_LEN + len;
+
+            if id == msg_id {
+                self.buffer.copy_within(end.., offset);
+                self.buffer.truncate(self.buffer.len() - (end - offset));
+
+                self.length -= 1;
+
+                return true;
+            }
+
+            offset = end;
+        }
+
+        false
+    }
+
     #[must_use]
     pub fn finalize(mut self) -> BytesMut {
         unsafe {
@@ -0,0 +1,462 @@
+//! Fake-TLS used by MTProxy with `ee` secrets: the connection starts with
+//! a handshake indistinguishable from TLS 1.3 with the domain of the secret,
+//! then all data is sent inside TLS application data records.
+
+use std::io;
+use std::pin::{Pin, pin};
+use std::task::{Context, Poll, ready};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::crypto::hmac_sha256;
+use crate::reader::ReaderDriver;
+use crate::utils::ready_ok;
+use crate::writer::WriterDriver;
+
+/// Length of the `ClientHello` record sent by the popular browsers.
+const CLIENT_HELLO_LEN: usize = 517;
+
+/// Offset of the `random` field in both hello records.
+const RANDOM: usize = 11;
+
+/// Largest payload of a single record.
+const MAX_RECORD_LEN: usize = 16384;
+
+const CHANGE_CIPHER_SPEC: [u8; 6] = [0x14, 0x03, 0x03, 0x00, 0x01, 0x01];
+
+const APPLICATION_DATA: [u8; 3] = [0x17, 0x03, 0x03];
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Build a `ClientHello` with the `random` field zeroed, padded to
+/// [`CLIENT_HELLO_LEN`] unless the `domain` is too long to fit.
+fn client_hello(domain: &str) -> io::Result<Vec<u8>> {
+    fn random<const N: usize>() -> [u8; N] {
+        let mut bytes = [0; N];
+        getrandom::fill(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn extension(hello: &mut Vec<u8>, typ: u16, data: &[u8]) {
+        hello.extend_from_slice(&typ.to_be_bytes());
+        hello.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        hello.extend_from_slice(data);
+    }
+
+    let mut hello = Vec::with_capacity(CLIENT_HELLO_LEN);
+
+    // Record and handshake headers, lengths are written afterward.
+    hello.extend_from_slice(&[0x16, 0x03, 0x01, 0, 0, 0x01, 0, 0, 0]);
+    hello.extend_from_slice(&[0x03, 0x03]);
+    hello.extend_from_slice(&[0; 32]);
+    hello.push(32);
+    hello.extend_from_slice(&random::<32>());
+
+    const CIPHER_SUITES: [u16; 15] = [
+        0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0xc013, 0xc014,
+        0x009c, 0x009d, 0x002f, 0x0035,
+    ];
+
+    hello.extend_from_slice(&(CIPHER_SUITES.len() as u16 * 2).to_be_bytes());
+
+    for suite in CIPHER_SUITES {
+        hello.extend_from_slice(&suite.to_be_bytes());
+    }
+
+    // No compression.
+    hello.extend_from_slice(&[0x01, 0x00]);
+
+    let extensions = hello.len();
+    hello.extend_from_slice(&[0, 0]);
+
+    let name = domain.as_bytes();
+    let mut server_name = Vec::with_capacity(name.len() + 5);
+    server_name.extend_from_slice(&(name.len() as u16 + 3).to_be_bytes());
+    server_name.push(0x00);
+    server_name.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    server_name.extend_from_slice(name);
+
+    let mut key_share = vec![0x00, 0x24, 0x00, 0x1d, 0x00, 0x20];
+    key_share.extend_from_slice(&random::<32>());
+
+    extension(&mut hello, 0x0000, &server_name);
+    extension(&mut hello, 0x0017, &[]);
+    extension(&mut hello, 0xff01, &[0x00]);
+    extension(
+        &mut hello,
+        0x000a,
+        &[0x00, 0x06, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18],
+    );
+    extension(&mut hello, 0x000b, &[0x01, 0x00]);
+    extension(&mut hello, 0x0023, &[]);
+    extension(&mut hello, 0x0010, b"\x00\x0c\x02h2\x08http/1.1");
+    extension(&mut hello, 0x0005, &[0x01, 0x00, 0x00, 0x00, 0x00]);
+    extension(
+        &mut hello,
+        0x000d,
+        &[
+            0x00, 0x10, 0x04, 0x03, 0x08, 0x04, 0x04, 0x01, 0x05, 0x03, 0x08, 0x05, 0x05, 0x01,
+            0x08, 0x06, 0x06, 0x01,
+        ],
+    );
+    extension(&mut hello, 0x0033, &key_share);
+    extension(&mut hello, 0x002d, &[0x01, 0x01]);
+    extension(&mut hello, 0x002b, &[0x04, 0x03, 0x04, 0x03, 0x03]);
+
+    let Some(padding) = CLIENT_HELLO_LEN.checked_sub(hello.len() + 4) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "fake-tls domain is too long",
+        ));
+    };
+
+    extension(&mut hello, 0x0015, &vec![0; padding]);
+
+    let len = hello.len();
+
+    hello[3..5].copy_from_slice(&(len as u16 - 5).to_be_bytes());
+    hello[6..9].copy_from_slice(&(len as u32 - 9).to_be_bytes()[1..]);
+    hello[extensions..extensions + 2]
+        .copy_from_slice(&((len - extensions - 2) as u16).to_be_bytes());
+
+    Ok(hello)
+}
+
+/// Read a single record with the header, checking its type and version.
+async fn read_record<R: ReaderDriver>(reader: &mut R, header: &[u8]) -> io::Result<Vec<u8>> {
+    let mut record = vec![0; 5];
+    reader.read_exact(&mut record).await?;
+
+    if !record.starts_with(header) {
+        return Err(invalid_data("unexpected fake-tls record"));
+    }
+
+    let len = u16::from_be_bytes([record[3], record[4]]) as usize;
+
+    record.resize(5 + len, 0);
+    reader.read_exact(&mut record[5..]).await?;
+
+    Ok(record)
+}
+
+/// Perform the handshake, returning drivers which send and receive
+/// the data inside TLS application data records.
+///
+/// The `random` field of the `ClientHello` contains its HMAC-SHA256
+/// with the `secret`, XORed with the current time; the `ServerHello`
+/// is verified the same way, including the client `random`.
+pub async fn connect<R: ReaderDriver, W: WriterDriver>(
+    mut reader: R,
+    mut writer: W,
+    secret: &[u8; 16],
+    domain: &str,
+) -> io::Result<(FakeTlsRead<R>, FakeTlsWrite<W>)> {
+    let mut hello = client_hello(domain)?;
+
+    let mut random = hmac_sha256(secret, &[&hello]);
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+
+    for (x, y) in random[28..32].iter_mut().zip(time.to_le_bytes()) {
+        *x ^= y;
+    }
+
+    hello[RANDOM..RANDOM + 32].copy_from_slice(&random);
+
+    writer.write_all(&hello).await?;
+    writer.flush().await?;
+
+    let mut response = read_record(&mut reader, &[0x16, 0x03, 0x03]).await?;
+    response.extend(read_record(&mut reader, &CHANGE_CIPHER_SPEC[..3]).await?);
+    response.extend(read_record(&mut reader, &APPLICATION_DATA).await?);
+
+    if response.len() < RANDOM + 32 {
+        return Err(invalid_data("fake-tls server hello is too short"));
+    }
+
+    let server_random: [u8; 32] = response[RANDOM..RANDOM + 32].try_into().unwrap();
+    response[RANDOM..RANDOM + 32].fill(0);
+
+    if hmac_sha256(secret, &[&random, &response]) != server_random {
+        return Err(invalid_data("fake-tls server hello digest mismatch"));
+    }
+
+    Ok((FakeTlsRead::new(reader), FakeTlsWrite::new(writer)))
+}
+
+/// Unwraps the payload of application data records received from the proxy.
+pub struct FakeTlsRead<R: ReaderDriver> {
+    driver: R,
+    header: [u8; 5],
+    header_pos: usize,
+    /// Payload bytes left in the current record.
+    remaining: usize,
+}
+
+impl<R: ReaderDriver> FakeTlsRead<R> {
+    fn new(driver: R) -> Self {
+        Self {
+            driver,
+            header: [0; 5],
+            header_pos: 0,
+            remaining: 0,
+        }
+    }
+
+    pub fn driver(&mut self) -> &mut R {
+        &mut self.driver
+    }
+}
+
+impl<R: ReaderDriver> AsyncRead for FakeTlsRead<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            if this.remaining == 0 {
+                let mut header = ReadBuf::new(&mut this.header[this.header_pos..]);
+
+                ready!(pin!(&mut this.driver).poll_read(cx, &mut header))?;
+
+                let n = header.filled().len();
+
+                if n == 0 {
+                    return Poll::Ready(Ok(()));
+                }
+
+                this.header_pos += n;
+
+                if this.header_pos < this.header.len() {
+                    continue;
+                }
+
+                if this.header[..3] != APPLICATION_DATA {
+                    return Poll::Ready(Err(invalid_data("unexpected fake-tls record")));
+                }
+
+                this.header_pos = 0;
+                this.remaining = u16::from_be_bytes([this.header[3], this.header[4]]) as usize;
+
+                continue;
+            }
+
+            let mut payload = buf.take(this.remaining);
+
+            ready!(pin!(&mut this.driver).poll_read(cx, &mut payload))?;
+
+            let n = payload.filled().len();
+
+            // SAFETY: `n` bytes were initialized by the driver.
+            unsafe { buf.assume_init(n) };
+            buf.advance(n);
+
+            this.remaining -= n;
+
+            return Poll::Ready(Ok(()));
+        }
+    }
+}
+
+/// Wraps each write into application data records sent to the proxy.
+///
+/// The record is buffered, and written out with the next write or flush.
+pub struct FakeTlsWrite<W: WriterDriver> {
+    driver: W,
+    record: Vec<u8>,
+    pos: usize,
+    /// Whether the `ChangeCipherSpec` record is yet to be sent.
+    init: bool,
+}
+
+impl<W: WriterDriver> FakeTlsWrite<W> {
+    fn new(driver: W) -> Self {
+        Self {
+            driver,
+            record: Vec::new(),
+            pos: 0,
+            init: true,
+        }
+    }
+
+    pub fn driver(&mut self) -> &mut W {
+        &mut self.driver
+    }
+
+    /// Wrap the payload into a record, returning the length wrapped.
+    fn record(&mut self, payload: &[u8]) -> usize {
+        let payload = &payload[..payload.len().min(MAX_RECORD_LEN)];
+
+        if std::mem::take(&mut self.init) {
+            self.record.extend_from_slice(&CHANGE_CIPHER_SPEC);
+        }
+
+        self.record.extend_from_slice(&APPLICATION_DATA);
+        self.record
+            .extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        self.record.extend_from_slice(payload);
+
+        self.pos = 0;
+
+        payload.len()
+    }
+
+    fn poll_record(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.pos < self.record.len() {
+            let n = ready_ok!(pin!(&mut self.driver).poll_write(cx, &self.record[self.pos..]));
+
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "wrote 0 bytes",
+                )));
+            }
+
+            self.pos += n;
+        }
+
+        self.record.clear();
+        self.pos = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: WriterDriver> AsyncWrite for FakeTlsWrite<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        ready_ok!(this.poll_record(cx));
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let len = this.record(buf);
+
+        // The rest of the record is written with the next write or flush.
+        if let Poll::Ready(Err(err)) = this.poll_record(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready_ok!(this.poll_record(cx));
+
+        pin!(&mut this.driver).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready_ok!(this.poll_record(cx));
+
+        pin!(&mut this.driver).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::net::{TcpListener, TcpStream};
+
+    const SECRET: [u8; 16] = [7; 16];
+
+    #[tokio::test]
+    async fn test_proxy_fake_tls() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut hello = read_record(&mut stream, &[0x16, 0x03, 0x01]).await.unwrap();
+
+            assert_eq!(hello.len(), CLIENT_HELLO_LEN);
+            assert!(hello.windows(11).any(|x| x == b"example.com"));
+
+            let client_random: [u8; 32] = hello[RANDOM..RANDOM + 32].try_into().unwrap();
+            hello[RANDOM..RANDOM + 32].fill(0);
+
+            let digest = hmac_sha256(&SECRET, &[&hello]);
+
+            assert_eq!(client_random[..28], digest[..28]);
+
+            let mut response = vec![0x16, 0x03, 0x03, 0x00, 0x2a, 0x02, 0x00, 0x00, 0x26];
+            response.extend_from_slice(&[0x03, 0x03]);
+            response.extend_from_slice(&[0; 32]);
+            response.extend_from_slice(&[0x00, 0x13, 0x01, 0x00]);
+            response.extend_from_slice(&CHANGE_CIPHER_SPEC);
+            response.extend_from_slice(&[0x17, 0x03, 0x03, 0x00, 0x04, 1, 2, 3, 4]);
+
+            let digest = hmac_sha256(&SECRET, &[&client_random, &response]);
+            response[RANDOM..RANDOM + 32].copy_from_slice(&digest);
+
+            stream.write_all(&response).await.unwrap();
+
+            let mut ccs = [0; 6];
+            stream.read_exact(&mut ccs).await.unwrap();
+
+            assert_eq!(ccs, CHANGE_CIPHER_SPEC);
+
+            // Echo the payload split into two records.
+            let record = read_record(&mut stream, &APPLICATION_DATA).await.unwrap();
+            let (a, b) = record[5..].split_at(10);
+
+            for payload in [a, b] {
+                stream.write_all(&APPLICATION_DATA).await.unwrap();
+                stream.write_u16(payload.len() as u16).await.unwrap();
+                stream.write_all(payload).await.unwrap();
+            }
+        });
+
+        let (r, w) = TcpStream::connect(addr).await.unwrap().into_split();
+
+        let (mut r, mut w) = connect(r, w, &SECRET, "example.com").await.unwrap();
+
+        let payload = [9u8; 64];
+
+        w.write_all(&payload).await.unwrap();
+        w.flush().await.unwrap();
+
+        let mut echo = [0; 64];
+        r.read_exact(&mut echo).await.unwrap();
+
+        assert_eq!(echo, payload);
+
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_proxy_fake_tls_client_hello() {
+        for len in [1, 100, 253] {
+            let hello = client_hello(&"a".repeat(len)).unwrap();
+            assert_eq!(hello.len(), CLIENT_HELLO_LEN);
+        }
+
+        let err = client_hello(&"a".repeat(400)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
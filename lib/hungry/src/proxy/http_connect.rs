@@ -0,0 +1,120 @@
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::utils::base64_encode;
+
+/// Longest response header accepted.
+const MAX_RESPONSE_HEADER_LEN: usize = 8192;
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Negotiate a tunnel to `host:port` through an HTTP proxy using the `CONNECT`
+/// method, optionally authenticating with a username and a password.
+///
+/// The response is read byte by byte to not consume any tunneled data.
+///
+/// ---
+/// https://datatracker.ietf.org/doc/html/rfc9110#section-9.3.6
+pub async fn connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    auth: Option<(&str, &str)>,
+) -> io::Result<()> {
+    let authority = if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    };
+
+    let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+
+    if let Some((username, password)) = auth {
+        let credentials = base64_encode(format!("{username}:{password}").as_bytes());
+
+        request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+    }
+
+    request.push_str("\r\n");
+
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() > MAX_RESPONSE_HEADER_LEN {
+            return Err(invalid_data("http proxy response is too long"));
+        }
+
+        response.push(stream.read_u8().await?);
+    }
+
+    let status = str::from_utf8(&response)
+        .ok()
+        .and_then(|response| response.split(' ').nth(1))
+        .ok_or_else(|| invalid_data("invalid http proxy response"))?;
+
+    match status {
+        "200" => Ok(()),
+        "407" => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "http proxy requires authentication",
+        )),
+        status => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("http proxy replied with {status}"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[tokio::test]
+    async fn test_proxy_http_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut request = Vec::new();
+
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+
+            assert_eq!(
+                request,
+                b"CONNECT 149.154.167.40:443 HTTP/1.1\r\n\
+                Host: 149.154.167.40:443\r\n\
+                Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\
+                \r\n"
+            );
+
+            stream
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\nhello")
+                .await
+                .unwrap();
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        connect(&mut stream, "149.154.167.40", 443, Some(("user", "pass")))
+            .await
+            .unwrap();
+
+        let mut hello = [0; 5];
+        stream.read_exact(&mut hello).await.unwrap();
+
+        assert_eq!(hello, *b"hello");
+
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,46 @@
+pub mod fake_tls;
+pub mod http_connect;
+mod mtproxy;
+pub mod socks5;
+
+use std::io;
+
+use tokio::net::TcpStream;
+
+pub use mtproxy::{InvalidMtProxySecret, MtProxySecret};
+
+/// Proxy used to establish the connection to the DC.
+#[derive(Clone, Debug)]
+pub enum Proxy {
+    Socks5 {
+        addr: String,
+        auth: Option<(String, String)>,
+    },
+    HttpConnect {
+        addr: String,
+        auth: Option<(String, String)>,
+    },
+}
+
+impl Proxy {
+    /// Connect to the proxy and negotiate a connection to `host:port`.
+    /// The stream can then be split and passed to [`init`].
+    ///
+    /// [`init`]: crate::init
+    pub async fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let (Proxy::Socks5 { addr, auth } | Proxy::HttpConnect { addr, auth }) = self;
+
+        let auth = auth.as_ref().map(|(u, p)| (u.as_str(), p.as_str()));
+
+        let mut stream = TcpStream::connect(addr).await?;
+
+        match self {
+            Proxy::Socks5 { .. } => socks5::connect(&mut stream, host, port, auth).await?,
+            Proxy::HttpConnect { .. } => {
+                http_connect::connect(&mut stream, host, port, auth).await?
+            }
+        }
+
+        Ok(stream)
+    }
+}
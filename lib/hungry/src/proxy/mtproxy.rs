@@ -0,0 +1,142 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::transport::{Obfuscated, PaddedIntermediate, Tagged};
+use crate::utils::base64_decode;
+
+/// MTProxy secret, as found in `tg://proxy` links, encoded
+/// either as hex or base64.
+///
+/// ---
+/// https://core.telegram.org/mtproto/mtproto-transports#transport-obfuscation
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MtProxySecret {
+    /// Obfuscation with any transport.
+    Plain([u8; 16]),
+    /// `dd` prefix: only the [`PaddedIntermediate`] transport is accepted.
+    Padded([u8; 16]),
+    /// `ee` prefix: the connection starts with a TLS handshake
+    /// imitating the `domain`, see [`fake_tls::connect`].
+    /// Only the [`PaddedIntermediate`] transport is accepted.
+    ///
+    /// [`fake_tls::connect`]: crate::proxy::fake_tls::connect
+    FakeTls { secret: [u8; 16], domain: String },
+}
+
+#[derive(Debug)]
+pub struct InvalidMtProxySecret;
+
+impl MtProxySecret {
+    /// The key mixed into the obfuscation keys.
+    pub fn secret(&self) -> &[u8; 16] {
+        use MtProxySecret::*;
+
+        match self {
+            Plain(secret) | Padded(secret) | FakeTls { secret, .. } => secret,
+        }
+    }
+
+    /// Wrap the `inner` transport to connect to the DC through the proxy.
+    pub fn obfuscated<T: Tagged>(&self, inner: T, dc_id: i16) -> Obfuscated<T> {
+        assert!(
+            matches!(self, Self::Plain(_)) || T::TAG == PaddedIntermediate::INIT,
+            "the proxy only accepts the padded intermediate transport"
+        );
+
+        Obfuscated::proxy(inner, dc_id, *self.secret())
+    }
+}
+
+impl FromStr for MtProxySecret {
+    type Err = InvalidMtProxySecret;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = match hex::decode(s) {
+            Ok(bytes) => bytes,
+            Err(_) => base64_decode(s).ok_or(InvalidMtProxySecret)?,
+        };
+
+        let secret = |bytes: &[u8]| -> Result<[u8; 16], InvalidMtProxySecret> {
+            bytes.try_into().map_err(|_| InvalidMtProxySecret)
+        };
+
+        // The length tells the plain secret apart from the prefixed ones,
+        // as it may start with either prefix too.
+        match bytes.as_slice() {
+            bytes if bytes.len() == 16 => Ok(Self::Plain(secret(bytes)?)),
+            [0xdd, bytes @ ..] if bytes.len() == 16 => Ok(Self::Padded(secret(bytes)?)),
+            [0xee, bytes @ ..] if bytes.len() > 16 => {
+                let (bytes, domain) = bytes.split_at(16);
+
+                let domain =
+                    String::from_utf8(domain.to_vec()).map_err(|_| InvalidMtProxySecret)?;
+
+                Ok(Self::FakeTls {
+                    secret: secret(bytes)?,
+                    domain,
+                })
+            }
+            _ => Err(InvalidMtProxySecret),
+        }
+    }
+}
+
+impl fmt::Display for InvalidMtProxySecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid mtproxy secret")
+    }
+}
+
+impl std::error::Error for InvalidMtProxySecret {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_mtproxy_secret() {
+        const SECRET: [u8; 16] = [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+            0xee, 0xff,
+        ];
+
+        let plain = "00112233445566778899aabbccddeeff".parse::<MtProxySecret>();
+        assert_eq!(plain.unwrap(), MtProxySecret::Plain(SECRET));
+
+        let padded = "dd00112233445566778899aabbccddeeff".parse::<MtProxySecret>();
+        assert_eq!(padded.unwrap(), MtProxySecret::Padded(SECRET));
+
+        // A plain secret starting with the prefix of the padded one.
+        let plain = "dd112233445566778899aabbccddeeff".parse::<MtProxySecret>();
+        assert_eq!(
+            plain.unwrap(),
+            MtProxySecret::Plain([
+                0xdd, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+                0xee, 0xff,
+            ])
+        );
+
+        let fake_tls =
+            "ee00112233445566778899aabbccddeeff6578616d706c652e636f6d".parse::<MtProxySecret>();
+        assert_eq!(
+            fake_tls.unwrap(),
+            MtProxySecret::FakeTls {
+                secret: SECRET,
+                domain: "example.com".to_string()
+            }
+        );
+
+        // The same secret encoded as base64url.
+        let fake_tls = "7gARIjNEVWZ3iJmqu8zd7v9leGFtcGxlLmNvbQ".parse::<MtProxySecret>();
+        assert_eq!(
+            fake_tls.unwrap(),
+            MtProxySecret::FakeTls {
+                secret: SECRET,
+                domain: "example.com".to_string()
+            }
+        );
+
+        assert!("00112233".parse::<MtProxySecret>().is_err());
+        assert!("dd0011223344".parse::<MtProxySecret>().is_err());
+    }
+}
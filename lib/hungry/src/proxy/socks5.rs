@@ -0,0 +1,228 @@
+use std::io;
+use std::net::IpAddr;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const VERSION: u8 = 0x05;
+
+const NO_AUTH: u8 = 0x00;
+const USERNAME_PASSWORD: u8 = 0x02;
+
+/// Version of the username/password subnegotiation.
+const AUTH_VERSION: u8 = 0x01;
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Negotiate a connection to `host:port` through a SOCKS5 proxy,
+/// optionally authenticating with a username and a password.
+///
+/// ---
+/// https://datatracker.ietf.org/doc/html/rfc1928
+/// https://datatracker.ietf.org/doc/html/rfc1929
+pub async fn connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    auth: Option<(&str, &str)>,
+) -> io::Result<()> {
+    let method = if auth.is_some() {
+        USERNAME_PASSWORD
+    } else {
+        NO_AUTH
+    };
+
+    stream.write_all(&[VERSION, 1, method]).await?;
+
+    let mut response = [0; 2];
+    stream.read_exact(&mut response).await?;
+
+    if response[0] != VERSION {
+        return Err(invalid_data("unexpected socks version"));
+    }
+
+    if response[1] != method {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "socks proxy rejected the authentication method",
+        ));
+    }
+
+    if let Some((username, password)) = auth {
+        let mut request = vec![AUTH_VERSION];
+
+        for field in [username, password] {
+            let len = u8::try_from(field.len()).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "socks credentials are too long",
+                )
+            })?;
+
+            request.push(len);
+            request.extend_from_slice(field.as_bytes());
+        }
+
+        stream.write_all(&request).await?;
+
+        stream.read_exact(&mut response).await?;
+
+        if response[0] != AUTH_VERSION {
+            return Err(invalid_data("unexpected socks auth version"));
+        }
+
+        if response[1] != 0x00 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "socks proxy rejected the credentials",
+            ));
+        }
+    }
+
+    let mut request = vec![VERSION, 0x01, 0x00];
+
+    match host.parse() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(0x01);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(0x04);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            let len = u8::try_from(host.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "host is too long"))?;
+
+            request.push(0x03);
+            request.push(len);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+
+    request.extend_from_slice(&port.to_be_bytes());
+
+    stream.write_all(&request).await?;
+
+    let mut response = [0; 4];
+    stream.read_exact(&mut response).await?;
+
+    if response[0] != VERSION {
+        return Err(invalid_data("unexpected socks version"));
+    }
+
+    if response[1] != 0x00 {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("socks proxy replied with {:#04x}", response[1]),
+        ));
+    }
+
+    // The bound address is not needed.
+    let len = match response[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => stream.read_u8().await? as usize,
+        _ => return Err(invalid_data("unexpected socks address type")),
+    };
+
+    let mut bound = vec![0; len + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[tokio::test]
+    async fn test_proxy_socks5() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut greeting = [0; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+
+            assert_eq!(greeting, [VERSION, 1, USERNAME_PASSWORD]);
+
+            stream
+                .write_all(&[VERSION, USERNAME_PASSWORD])
+                .await
+                .unwrap();
+
+            let mut auth = [0; 12];
+            stream.read_exact(&mut auth).await.unwrap();
+
+            assert_eq!(auth, *b"\x01\x04user\x05pass!");
+
+            stream.write_all(&[AUTH_VERSION, 0x00]).await.unwrap();
+
+            let mut request = [0; 10];
+            stream.read_exact(&mut request).await.unwrap();
+
+            assert_eq!(request, [VERSION, 1, 0, 1, 149, 154, 167, 40, 0x01, 0xbb]);
+
+            stream
+                .write_all(&[VERSION, 0, 0, 1, 127, 0, 0, 1, 0x12, 0x34])
+                .await
+                .unwrap();
+
+            // The connection is now relayed.
+            stream.write_all(b"hello").await.unwrap();
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        connect(&mut stream, "149.154.167.40", 443, Some(("user", "pass!")))
+            .await
+            .unwrap();
+
+        let mut hello = [0; 5];
+        stream.read_exact(&mut hello).await.unwrap();
+
+        assert_eq!(hello, *b"hello");
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_proxy_socks5_auth_version() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut greeting = [0; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+
+            stream
+                .write_all(&[VERSION, USERNAME_PASSWORD])
+                .await
+                .unwrap();
+
+            let mut auth = [0; 12];
+            stream.read_exact(&mut auth).await.unwrap();
+
+            // Answered with the socks version instead.
+            stream.write_all(&[VERSION, 0x00]).await.unwrap();
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let err = connect(&mut stream, "149.154.167.40", 443, Some(("user", "pass!")))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        server.await.unwrap();
+    }
+}
@@ -22,6 +22,8 @@ pub struct Reader<R: ReaderDriver, T: Transport> {
     buffer: BytesMut,
     pos: usize,
     end: usize,
+    /// Bytes read past the last unpacked packet.
+    ahead: Vec<u8>,
 }
 
 impl<R: ReaderDriver, T: Transport> Reader<R, T> {
@@ -34,6 +36,7 @@ impl<R: ReaderDriver, T: Transport> Reader<R, T> {
             buffer,
             pos: 0,
             end: T::Read::DEFAULT_BUF_LEN,
+            ahead: Vec::new(),
         }
     }
 
@@ -47,6 +50,16 @@ impl<R: ReaderDriver, T: Transport> Reader<R, T> {
         self.end = T::Read::DEFAULT_BUF_LEN;
     }
 
+    /// Keep the bytes read past the unpacked packet for the next one.
+    fn keep_ahead(&mut self) {
+        if let Some(len) = self.transport.consumed()
+            && len < self.buffer.len()
+        {
+            self.ahead.extend_from_slice(&self.buffer[len..]);
+            self.buffer.truncate(len);
+        }
+    }
+
     pub fn poll(
         &mut self,
         cx: &mut Context<'_>,
@@ -57,6 +70,16 @@ impl<R: ReaderDriver, T: Transport> Reader<R, T> {
             "buffer length have been modified externally",
         );
 
+        if self.pos == 0 && !self.ahead.is_empty() {
+            if self.buffer.capacity() < self.ahead.len() {
+                return Poll::Ready(ControlFlow::Break(self.ahead.len()));
+            }
+
+            self.buffer.extend_from_slice(&self.ahead);
+            self.pos = self.ahead.len();
+            self.ahead.clear();
+        }
+
         loop {
             if self.buffer.capacity() < self.end {
                 return Poll::Ready(ControlFlow::Break(self.end));
@@ -75,6 +98,7 @@ impl<R: ReaderDriver, T: Transport> Reader<R, T> {
                     continue;
                 }
                 ControlFlow::Break(Err(err)) => {
+                    self.keep_ahead();
                     self.reset();
 
                     self.buffer.clear();
@@ -84,6 +108,7 @@ impl<R: ReaderDriver, T: Transport> Reader<R, T> {
                 ControlFlow::Break(Ok(unpack)) => unpack,
             };
 
+            self.keep_ahead();
             self.reset();
 
             return Poll::Ready(ControlFlow::Continue(Ok(unpack)));
@@ -97,8 +122,10 @@ impl<R: ReaderDriver, T: Transport> Reader<R, T> {
             return Poll::Ready(Ok(()));
         }
 
+        let limit = (length + T::Read::READ_AHEAD).min(self.buffer.capacity());
+
         loop {
-            let len = length - self.buffer.len();
+            let len = limit - self.buffer.len();
             let mut buf = ReadBuf::uninit(&mut self.buffer.spare_capacity_mut()[..len]);
 
             ready_ok!(pin!(&mut self.driver).poll_read(cx, &mut buf));
@@ -125,7 +152,7 @@ impl<R: ReaderDriver, T: Transport> Reader<R, T> {
             // SAFETY: data is initialized up to `self.pos` bytes.
             unsafe { self.buffer.set_len(self.pos) };
 
-            if n < len {
+            if self.buffer.len() < length {
                 continue;
             }
 
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+/// Configuration of the [`Sender`].
+///
+/// [`Sender`]: crate::Sender
+#[derive(Clone, Debug)]
+pub struct SenderConfig {
+    /// Functions serialized to more bytes than the threshold are sent
+    /// as `gzip_packed` if compressing makes them smaller.
+    /// `None` disables the compression.
+    pub gzip_threshold: Option<usize>,
+
+    /// Number of future salts to request with `get_future_salts` before the
+    /// known ones run out, to switch salts without being rejected by the server.
+    /// `None` disables the requests.
+    pub future_salts: Option<i32>,
+
+    /// Interval of the `ping_delay_disconnect` keepalive pings.
+    /// `None` disables them.
+    pub ping_interval: Option<Duration>,
+    /// Time to wait for the `pong` before the connection is reported dead.
+    /// The server closes the connection if no ping is received for
+    /// the interval and the timeout.
+    pub ping_timeout: Duration,
+
+    /// Functions waiting for the response for longer have their state
+    /// requested with `msgs_state_req`, to be resent if the server has not
+    /// received them. `None` disables the requests.
+    pub state_req_delay: Option<Duration>,
+
+    /// Acknowledgements of the received messages are sent along with the
+    /// next outgoing messages, or on their own after the delay.
+    pub ack_delay: Duration,
+    /// Acknowledgements are sent without delay once this many are pending.
+    pub max_pending_acks: usize,
+
+    /// Capacity of the buffers the outgoing messages are packed into,
+    /// which must fit the fixed-length service messages.
+    /// Messages too large to fit are sent on their own.
+    pub container_capacity: usize,
+    /// Maximum number of messages packed into a container,
+    /// from 1 up to the 1020 accepted by the server.
+    pub max_container_len: usize,
+    /// Time to wait for more messages to pack along with the queued ones
+    /// before sending them. Zero sends them as soon as the connection is idle.
+    pub flush_delay: Duration,
+}
+
+impl Default for SenderConfig {
+    fn default() -> Self {
+        Self {
+            gzip_threshold: Some(512),
+
+            future_salts: Some(32),
+
+            ping_interval: Some(Duration::from_secs(60)),
+            ping_timeout: Duration::from_secs(15),
+
+            state_req_delay: Some(Duration::from_secs(30)),
+
+            ack_delay: Duration::from_millis(500),
+            max_pending_acks: 1024,
+
+            container_capacity: 1024 * 1024,
+            max_container_len: 1020,
+            flush_delay: Duration::ZERO,
+        }
+    }
+}
+
+/// Configuration of a function invoked with [`Sender::invoke_with_config`].
+///
+/// [`Sender::invoke_with_config`]: crate::Sender::invoke_with_config
+#[derive(Clone, Debug)]
+pub struct InvokeConfig {
+    /// The server is requested to notify about receiving the message
+    /// with [`SenderEvent::QuickAck`] before processing it.
+    ///
+    /// [`SenderEvent::QuickAck`]: crate::SenderEvent::QuickAck
+    pub quick_ack: bool,
+    /// The function fails with [`SenderError::Timeout`] if not answered in time.
+    /// `None` waits for the response indefinitely.
+    ///
+    /// [`SenderError::Timeout`]: crate::SenderError::Timeout
+    pub timeout: Option<Duration>,
+    /// Number of times the function is sent again after reconnecting or when
+    /// rejected with `bad_msg_notification`, before failing with
+    /// [`InvokeError::Retries`].
+    ///
+    /// [`InvokeError::Retries`]: crate::InvokeError::Retries
+    pub max_retries: u32,
+}
+
+impl Default for InvokeConfig {
+    fn default() -> Self {
+        Self {
+            quick_ack: false,
+            timeout: None,
+            max_retries: 5,
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use bytes::BytesMut;
 
-use crate::mtproto::{EncryptedEnvelope, Msg};
+use crate::mtproto::{EncryptedEnvelope, Msg, MsgId};
 use crate::pack::MsgContainer;
 use crate::transport::Transport;
 use crate::{Envelope, tl};
@@ -9,10 +9,14 @@ pub(super) struct Container<T: Transport> {
     transport: Envelope<T>,
     encrypted: EncryptedEnvelope,
     container: MsgContainer,
+    quick_ack: Vec<MsgId>,
+    msg_ids: Vec<MsgId>,
+    /// Maximum number of messages.
+    max_len: usize,
 }
 
 impl<T: Transport> Container<T> {
-    pub(crate) fn new(mut buffer: BytesMut) -> Container<T> {
+    pub(crate) fn new(mut buffer: BytesMut, max_len: usize) -> Container<T> {
         let transport = Envelope::split(&mut buffer);
         let encrypted = Envelope::split(&mut buffer);
 
@@ -20,6 +24,9 @@ impl<T: Transport> Container<T> {
             container: MsgContainer::new(buffer),
             transport,
             encrypted,
+            quick_ack: Vec::new(),
+            msg_ids: Vec::new(),
+            max_len,
         }
     }
 
@@ -35,18 +42,81 @@ impl<T: Transport> Container<T> {
 
     #[inline(always)]
     pub(super) fn can_push(&self, len: usize) -> bool {
-        self.container.can_push(len)
+        self.container.len() < self.max_len && self.container.can_push(len)
     }
 
-    pub(super) fn push<X: tl::Function>(
+    /// Returns the length of the largest message that can still be pushed, if any.
+    #[inline(always)]
+    pub(super) fn spare_capacity(&self) -> Option<usize> {
+        if self.container.len() < self.max_len {
+            self.container.spare_capacity()
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if a message of the length fits into an empty container.
+    #[inline(always)]
+    pub(super) fn fits(&self, len: usize) -> bool {
+        self.container.fits(len)
+    }
+
+    pub(super) fn push<X: tl::ser::SerializeUnchecked + tl::Identifiable>(
         &mut self,
         msg: Msg,
         x: tl::CalculatedLen<'_, tl::ConstructorId<X>>,
+        quick_ack: bool,
     ) {
+        if quick_ack {
+            self.quick_ack.push(msg.msg_id);
+        }
+
+        self.msg_ids.push(msg.msg_id);
+
         self.container.push(msg, x);
     }
 
-    pub(super) fn finalize(self) -> (Envelope<T>, EncryptedEnvelope, BytesMut) {
-        (self.transport, self.encrypted, self.container.finalize())
+    pub(super) fn push_serialized(&mut self, msg: Msg, body: &[u8], quick_ack: bool) {
+        if quick_ack {
+            self.quick_ack.push(msg.msg_id);
+        }
+
+        self.msg_ids.push(msg.msg_id);
+
+        self.container.push_serialized(msg, body);
+    }
+
+    /// Remove the message if not sent yet. Returns `false` if not pushed.
+    pub(super) fn remove(&mut self, msg_id: MsgId) -> bool {
+        if !self.container.remove(msg_id) {
+            return false;
+        }
+
+        self.quick_ack.retain(|&id| id != msg_id);
+        self.msg_ids.retain(|&id| id != msg_id);
+
+        true
+    }
+
+    /// Returns the buffer along with the identifiers of the messages
+    /// with quick ack requested and of all the messages.
+    pub(super) fn finalize(
+        self,
+    ) -> (
+        Envelope<T>,
+        EncryptedEnvelope,
+        BytesMut,
+        Vec<MsgId>,
+        Vec<MsgId>,
+    ) {
+        let buffer = self.container.finalize();
+
+        (
+            self.transport,
+            self.encrypted,
+            buffer,
+            self.quick_ack,
+            self.msg_ids,
+        )
     }
 }
@@ -1,7 +1,11 @@
-use std::fmt;
+use std::time::Duration;
+use std::{fmt, io};
+
+use bytes::BytesMut;
 
 use crate::mtproto::{MsgKeyCheckError, PlainMessage, Session};
 use crate::reader::ReaderError;
+use crate::tl;
 use crate::writer::WriterError;
 
 #[derive(Debug)]
@@ -13,6 +17,23 @@ pub enum SenderError {
     UnexpectedAuthKeyId(i64),
     MsgKeyCheck(MsgKeyCheckError),
     UnexpectedSessionId(Session),
+    InvalidMsgLength(i32),
+    InvalidMsgId(i64),
+    Deserialization(tl::de::Error),
+    GzipPacked(io::Error),
+
+    /// The server does not know the auth key; it must be re-created.
+    AuthKeyNotFound,
+    /// Too many connections or messages; reconnect after the `backoff`.
+    TransportFlood {
+        backoff: Duration,
+    },
+    /// The connection was made to a nonexistent DC.
+    InvalidDc,
+    /// The keepalive ping was not answered in time; the connection is likely dead.
+    PingTimeout,
+    /// The function was not answered before its deadline.
+    Timeout,
 }
 
 impl From<ReaderError> for SenderError {
@@ -36,6 +57,13 @@ impl From<MsgKeyCheckError> for SenderError {
     }
 }
 
+impl From<tl::de::Error> for SenderError {
+    #[inline]
+    fn from(value: tl::de::Error) -> Self {
+        Self::Deserialization(value)
+    }
+}
+
 impl fmt::Display for SenderError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use SenderError::*;
@@ -49,6 +77,15 @@ impl fmt::Display for SenderError {
             UnexpectedAuthKeyId(err) => write!(f, "unexpected auth key id: {err:#010x}"),
             MsgKeyCheck(err) => err.fmt(f),
             UnexpectedSessionId(err) => write!(f, "unexpected session id: {err:#010x}"),
+            InvalidMsgLength(len) => write!(f, "invalid msg length: {len}"),
+            InvalidMsgId(msg_id) => write!(f, "invalid msg id: {msg_id:#018x}"),
+            Deserialization(err) => err.fmt(f),
+            GzipPacked(err) => write!(f, "invalid gzip packed object: {err}"),
+            AuthKeyNotFound => write!(f, "auth key not found, it must be re-created"),
+            TransportFlood { backoff } => write!(f, "transport flood, retry after {backoff:?}"),
+            InvalidDc => write!(f, "invalid dc"),
+            PingTimeout => write!(f, "ping timeout, the connection is likely dead"),
+            Timeout => write!(f, "timed out"),
         }
     }
 }
@@ -61,7 +98,63 @@ impl std::error::Error for SenderError {
             Writer(err) => err,
             Reader(err) => err,
             MsgKeyCheck(err) => err,
+            Deserialization(err) => err,
+            GzipPacked(err) => err,
             _ => return None,
         })
     }
 }
+
+#[derive(Debug)]
+pub enum InvokeError {
+    Rpc(tl::mtproto::types::RpcError),
+    Deserialization {
+        source: tl::de::Error,
+        buffer: BytesMut,
+    },
+    /// The server rejected the message with the `bad_msg_notification`
+    /// error code and it cannot be resent.
+    BadMsgNotification {
+        code: i32,
+    },
+    /// The function failed because of the [`Sender`], e.g. with
+    /// [`SenderError::Timeout`] if not answered before the deadline.
+    ///
+    /// [`Sender`]: crate::Sender
+    Sender(SenderError),
+    /// The function was sent again too many times.
+    Retries,
+    /// The [`Sender`] was dropped before the response was received.
+    ///
+    /// [`Sender`]: crate::Sender
+    Dropped,
+}
+
+impl fmt::Display for InvokeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use InvokeError::*;
+
+        f.write_str("invoke error: ")?;
+
+        match self {
+            Rpc(err) => write!(f, "rpc error {}: {}", err.error_code, err.error_message),
+            Deserialization { source, .. } => source.fmt(f),
+            BadMsgNotification { code } => write!(f, "bad msg notification: {code}"),
+            Sender(err) => err.fmt(f),
+            Retries => write!(f, "too many retries"),
+            Dropped => write!(f, "sender was dropped"),
+        }
+    }
+}
+
+impl std::error::Error for InvokeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use InvokeError::*;
+
+        match self {
+            Deserialization { source, .. } => Some(source),
+            Sender(err) => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use std::time::Duration;
+
+use crate::mtproto::MsgId;
+
+#[derive(Debug)]
+pub enum SenderEvent {
+    /// The server has received the messages sent with quick ack requested.
+    /// The messages are yet to be processed.
+    QuickAck(Vec<MsgId>),
+    /// The server has created a new session, and updates may have been lost.
+    /// The application must call `updates.getDifference`.
+    UpdatesGap,
+    /// The keepalive ping was answered after the round-trip time.
+    Pong { rtt: Duration },
+}
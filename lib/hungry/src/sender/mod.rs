@@ -1,23 +1,53 @@
+mod config;
 mod container;
 mod error;
+mod event;
+mod ordered;
+mod replay_window;
+mod request;
+mod response;
+mod salts;
 
+use std::collections::{HashMap, VecDeque};
 use std::mem;
 use std::ops::ControlFlow;
+use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
+use tokio::sync::mpsc;
+use tokio::time::{self, Instant, Sleep};
 
+use crate::gzip_packed::GzipPacked;
 use crate::mtproto::{
-    AuthKey, DecryptedMessage, EncryptedMessage, Message, Msg, MsgId, MsgIds, Salt, SeqNos, Session,
+    AuthKey, DecryptedMessage, EncryptedEnvelopeSize, EncryptedMessage, Message, Msg, MsgId,
+    MsgIds, Salt, SeqNos, Session,
 };
-use crate::reader::{Reader, ReaderDriver};
+use crate::reader::{Reader, ReaderDriver, ReaderError};
 use crate::tl;
-use crate::transport::{Packet, Transport, Unpack};
+use crate::tl::Identifiable;
+use crate::tl::ser::SerializeInto;
+use crate::transport::{Packet, QuickAck, Transport, TransportError, TransportStatus, Unpack};
 use crate::writer::{QueuedWriter, WriterDriver};
+use crate::{Envelope, EnvelopeSize, pack, unpack};
 
 use container::Container;
+use replay_window::ReplayWindow;
+use request::Request;
+use salts::Salts;
 
-pub use error::SenderError;
+pub use config::{InvokeConfig, SenderConfig};
+pub use error::{InvokeError, SenderError};
+pub use event::SenderEvent;
+pub use ordered::OrderedBatch;
+pub use response::Response;
+
+/// `rpc_result#f35c6d01 req_msg_id:long result:Object = RpcResult;`
+const RPC_RESULT: u32 = 0xf35c6d01;
+
+/// `invokeAfterMsg#cb9f372d {X:Type} msg_id:long query:!X = X;`
+const INVOKE_AFTER_MSG: u32 = 0xcb9f372d;
 
 pub struct Sender<T: Transport, R: ReaderDriver, W: WriterDriver> {
     reader: Reader<R, T>,
@@ -26,167 +56,3337 @@ pub struct Sender<T: Transport, R: ReaderDriver, W: WriterDriver> {
     auth_key: AuthKey,
     salt: Salt,
     session_id: Session,
+    /// Session replaced by a new one, whose messages still arriving are ignored.
+    old_session_id: Option<Session>,
+
+    config: SenderConfig,
+
+    /// Future salts to switch to over time.
+    salts: Salts,
+    /// Pending `get_future_salts` request.
+    future_salts: Option<Response<tl::mtproto::funcs::GetFutureSalts>>,
+    /// Server time before which `get_future_salts` is not retried after a failure.
+    future_salts_retry: i32,
+
+    /// Pending keepalive ping with the time it was sent.
+    ping: Option<(Response<tl::mtproto::funcs::PingDelayDisconnect>, Instant)>,
+    /// Deadline for the next keepalive ping, or for the `pong` if pending.
+    ping_timer: Option<Pin<Box<Sleep>>>,
+    /// Round-trip time measured with the last keepalive ping.
+    rtt: Option<Duration>,
 
     container: Container<T>,
+    /// Deadline for sending the current container.
+    flush_timer: Option<Pin<Box<Sleep>>>,
 
     msg_ids: MsgIds,
     seq_nos: SeqNos,
+
+    /// Identifiers of the last received messages.
+    replay_window: ReplayWindow,
+
+    /// Messages awaiting quick ack by the expected token.
+    quick_acks: HashMap<u32, Vec<MsgId>>,
+
+    /// Functions awaiting `rpc_result`.
+    pending: HashMap<MsgId, Request>,
+    /// Functions by the containers they were sent in, to be resent if rejected.
+    containers: HashMap<MsgId, Vec<MsgId>>,
+    /// Current identifiers of the functions sent again, by the first ones.
+    resent: HashMap<MsgId, MsgId>,
+
+    /// Notified by the dropped [`Response`]s with the first identifiers.
+    cancel: mpsc::UnboundedSender<MsgId>,
+    cancelled: mpsc::UnboundedReceiver<MsgId>,
+    /// Pending `rpc_drop_answer`s of the cancelled functions.
+    drop_answers: Vec<Response<tl::mtproto::funcs::RpcDropAnswer>>,
+    /// Deadline of the function to time out first.
+    timeout_timer: Option<Pin<Box<Sleep>>>,
+
+    /// Pending `msgs_state_req` with the identifiers of the queried functions.
+    state_req: Option<(MsgId, Vec<MsgId>)>,
+    /// Deadline for checking the functions waiting for too long.
+    state_timer: Option<Pin<Box<Sleep>>>,
+
+    /// Received content-related messages yet to be acknowledged.
+    acks: Vec<MsgId>,
+    /// Acknowledgements by the containers they were sent in, to be sent again if rejected.
+    sent_acks: VecDeque<(MsgId, Vec<MsgId>)>,
+    /// Deadline for sending the pending acknowledgements.
+    ack_timer: Option<Pin<Box<Sleep>>>,
+
+    /// Events to be returned by the next polls.
+    events: VecDeque<SenderEvent>,
+
+    /// Backoff for the next transport flood, doubled on each consecutive one.
+    flood_backoff: Duration,
 }
 
+/// Seconds to wait before retrying a failed `get_future_salts`.
+const FUTURE_SALTS_RETRY_DELAY: i32 = 60;
+
+/// Number of received message identifiers remembered to detect replays.
+const REPLAY_WINDOW: usize = 1024;
+
+/// Received messages created more than this many seconds before the server time
+/// or after it are ignored.
+const MAX_MSG_AGE: i32 = 300;
+const MAX_MSG_AHEAD: i32 = 30;
+
+/// Received messages are padded with 12 to 1024 random bytes.
+const MIN_PADDING_LEN: usize = 12;
+const MAX_PADDING_LEN: usize = 1024;
+
+/// Maximum number of messages in a container accepted by the server.
+const MAX_CONTAINER_LEN: usize = 1020;
+
+/// Length of the largest service message of a fixed length,
+/// `msg_resend_req` with one identifier.
+const MAX_SERVICE_LEN: usize = 20;
+
+/// Length of `msgs_ack` without the identifiers.
+const MSGS_ACK_HEADER_LEN: usize = 12;
+
+const MIN_FLOOD_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_FLOOD_BACKOFF: Duration = Duration::from_secs(64);
+
 impl<T: Transport, R: ReaderDriver, W: WriterDriver> Sender<T, R, W> {
-    /// FIXME
-    fn new_container() -> Container<T> {
-        let buffer = BytesMut::with_capacity(1024 * 1024);
+    fn new_container(config: &SenderConfig) -> Container<T> {
+        let buffer = BytesMut::with_capacity(config.container_capacity);
 
-        Container::new(buffer)
+        Container::new(buffer, config.max_container_len)
     }
 
     fn get_container(&mut self, len: usize) -> &mut Container<T> {
         if !self.container.can_push(len) {
-            let container = Self::new_container();
-
-            let container = mem::replace(&mut self.container, container);
-
-            self.queue(container);
+            self.flush();
         }
 
         &mut self.container
     }
 
+    /// The `time_offset` is returned along with the auth key and salt
+    /// by [`SetClientDhParams::dh_gen_ok`], and corrected from the messages
+    /// received afterwards.
+    ///
+    /// [`SetClientDhParams::dh_gen_ok`]: crate::auth::SetClientDhParams::dh_gen_ok
     pub fn new(
         reader: Reader<R, T>,
         writer: QueuedWriter<W, T>,
         auth_key: AuthKey,
         salt: Salt,
+        time_offset: i32,
+        session_id: Session,
+    ) -> Self {
+        Self::with_config(
+            reader,
+            writer,
+            auth_key,
+            salt,
+            time_offset,
+            session_id,
+            SenderConfig::default(),
+        )
+    }
+
+    /// Same as [`new`], but with a custom configuration.
+    ///
+    /// Panics if the container length is out of range,
+    /// or the containers cannot fit the service messages.
+    ///
+    /// [`new`]: Self::new
+    pub fn with_config(
+        reader: Reader<R, T>,
+        writer: QueuedWriter<W, T>,
+        auth_key: AuthKey,
+        salt: Salt,
+        time_offset: i32,
         session_id: Session,
+        config: SenderConfig,
     ) -> Self {
+        assert!(
+            (1..=MAX_CONTAINER_LEN).contains(&config.max_container_len),
+            "max_container_len is out of range"
+        );
+        assert!(
+            Self::new_container(&config).fits(MAX_SERVICE_LEN),
+            "container_capacity is too small"
+        );
+
+        let (cancel, cancelled) = mpsc::unbounded_channel();
+
+        let mut msg_ids = MsgIds::new();
+        msg_ids.set_time_offset(time_offset);
+
         Self {
             reader,
             writer,
 
-            container: Self::new_container(),
+            salts: Salts::new(),
+            future_salts: None,
+            future_salts_retry: 0,
 
-            msg_ids: MsgIds::new(),
+            ping: None,
+            ping_timer: None,
+            rtt: None,
+
+            container: Self::new_container(&config),
+            flush_timer: None,
+
+            msg_ids,
             seq_nos: SeqNos::new(),
 
+            replay_window: ReplayWindow::new(REPLAY_WINDOW),
+
+            quick_acks: HashMap::new(),
+
+            pending: HashMap::new(),
+            containers: HashMap::new(),
+
+            resent: HashMap::new(),
+            cancel,
+            cancelled,
+            drop_answers: Vec::new(),
+            timeout_timer: None,
+
+            state_req: None,
+            state_timer: None,
+
+            acks: Vec::new(),
+            sent_acks: VecDeque::new(),
+            ack_timer: None,
+
+            events: VecDeque::new(),
+
+            flood_backoff: MIN_FLOOD_BACKOFF,
+
             auth_key,
             salt,
             session_id,
+            old_session_id: None,
+
+            config,
         }
     }
 
+    /// Seconds added to the local time to approximate the server time.
+    #[inline]
+    pub fn time_offset(&self) -> i32 {
+        self.msg_ids.time_offset()
+    }
+
+    /// Set the time offset, e.g. to [`MsgIds::time_offset_for`] a known server
+    /// time, for the message identifiers to be accepted by the server even
+    /// if the local clock is off.
+    ///
+    /// The offset is then corrected from the messages received.
+    #[inline]
+    pub fn set_time_offset(&mut self, time_offset: i32) {
+        self.msg_ids.set_time_offset(time_offset);
+    }
+
+    /// Round-trip time measured with the last keepalive ping.
+    #[inline]
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
     pub fn invoke<X: tl::Function>(
         &mut self,
         func: tl::CalculatedLen<'_, tl::ConstructorId<X>>,
-    ) -> MsgId {
-        let msg = Msg {
-            msg_id: self.msg_ids.get_using_system_time(),
-            seq_no: self.seq_nos.get_content_related(),
+    ) -> Response<X> {
+        self.invoke_with_config(func, InvokeConfig::default())
+    }
+
+    /// Same as [`invoke`], but the server is requested to notify about
+    /// receiving the message with [`SenderEvent::QuickAck`] before processing it.
+    ///
+    /// [`invoke`]: Self::invoke
+    pub fn invoke_with_quick_ack<X: tl::Function>(
+        &mut self,
+        func: tl::CalculatedLen<'_, tl::ConstructorId<X>>,
+    ) -> Response<X> {
+        let config = InvokeConfig {
+            quick_ack: true,
+            ..InvokeConfig::default()
         };
 
-        let msg_id = msg.msg_id;
+        self.invoke_with_config(func, config)
+    }
 
-        self.get_container(func.len()).push(msg, func);
+    /// Same as [`invoke`], with a deadline and the number of retries.
+    ///
+    /// [`invoke`]: Self::invoke
+    pub fn invoke_with_config<X: tl::Function>(
+        &mut self,
+        func: tl::CalculatedLen<'_, tl::ConstructorId<X>>,
+        config: InvokeConfig,
+    ) -> Response<X> {
+        self.invoke_after(func, config, None)
+    }
 
-        msg_id
+    /// Start a batch of functions to be executed in order.
+    pub fn ordered(&mut self) -> OrderedBatch<'_, T, R, W> {
+        OrderedBatch::new(self)
     }
 
-    fn queue(&mut self, container: Container<T>) {
-        let message = DecryptedMessage {
-            salt: self.salt,
-            session_id: self.session_id,
+    /// Invoke a function needed by the sender itself, sent again as many
+    /// times as needed.
+    fn invoke_internal<X: tl::Function>(
+        &mut self,
+        func: tl::CalculatedLen<'_, tl::ConstructorId<X>>,
+    ) -> Response<X> {
+        let config = InvokeConfig {
+            max_retries: u32::MAX,
+            ..InvokeConfig::default()
+        };
+
+        self.invoke_with_config(func, config)
+    }
+
+    /// Invoke the function to be executed after the one sent in the `after` message.
+    fn invoke_after<X: tl::Function>(
+        &mut self,
+        func: tl::CalculatedLen<'_, tl::ConstructorId<X>>,
+        config: InvokeConfig,
+        after: Option<MsgId>,
+    ) -> Response<X> {
+        let mut body = Vec::with_capacity(func.len());
+        body.ser(&*func);
+
+        if let Some(threshold) = self.config.gzip_threshold
+            && body.len() > threshold
+        {
+            let gzip_packed = GzipPacked::pack(&body);
+            let packed = tl::CalculatedLen::new(tl::ConstructorId::from_ref(&gzip_packed));
+
+            // Incompressible data only grows.
+            if packed.len() < body.len() {
+                body.clear();
+                body.ser(&*packed);
+            }
+        }
+
+        if let Some(after) = after {
+            let query = mem::take(&mut body);
+
+            body.reserve(12 + query.len());
+            body.extend_from_slice(&INVOKE_AFTER_MSG.to_le_bytes());
+            body.extend_from_slice(&after.to_le_bytes());
+            body.extend_from_slice(&query);
+        }
+
+        let msg_id = self.msg_ids.get_using_system_time();
+
+        let (sender, response) = Response::new(msg_id, self.cancel.clone());
+
+        let now = Instant::now();
+        let deadline = config.timeout.map(|timeout| now + timeout);
+
+        let request = Request {
+            msg_id,
+            body,
+            quick_ack: config.quick_ack,
+            container: None,
+            received: false,
+            sent: now,
+            deadline,
+            retries: config.max_retries,
+            after,
+            sender,
         };
 
+        self.push_request(msg_id, request);
+
+        if let Some(deadline) = deadline {
+            self.set_deadline(deadline);
+        }
+
+        response
+    }
+
+    fn push_request(&mut self, msg_id: MsgId, mut request: Request) {
+        request.sent = Instant::now();
+
         let msg = Msg {
-            msg_id: self.msg_ids.get_using_system_time(),
-            seq_no: self.seq_nos.non_content_related(),
+            msg_id,
+            seq_no: self.seq_nos.get_content_related(),
         };
 
-        let (transport, mtp, buffer) = container.finalize();
+        if self.container.fits(request.body.len()) {
+            self.get_container(request.body.len()).push_serialized(
+                msg,
+                &request.body,
+                request.quick_ack,
+            );
+        } else {
+            request.container = None;
+            self.queue_standalone(msg, &request.body, request.quick_ack);
+        }
 
-        let (h, f) = self
-            .writer
-            .queue(transport, mtp, buffer, &self.auth_key, message, msg);
+        self.pending.insert(msg_id, request);
+    }
 
-        if let Some(_h) = h {}
+    /// Send the rejected function, or all functions of the rejected container, again.
+    ///
+    /// Only a `retry`, i.e. after a connection loss or `bad_msg_notification`,
+    /// counts against the retries of the functions.
+    fn resend(&mut self, msg_id: MsgId, retry: bool) {
+        self.resend_acks(msg_id);
 
-        if let Some(_f) = f {}
+        let mut msg_ids = self
+            .containers
+            .remove(&msg_id)
+            .unwrap_or_else(|| vec![msg_id]);
+
+        // Functions executed in order are resent in order.
+        msg_ids.sort_unstable();
+
+        self.resend_all(msg_ids.into_iter().map(|msg_id| (msg_id, retry)).collect());
     }
 
-    fn unpack(&'_ mut self, unpack: Unpack) -> Result<(), SenderError> {
-        let data = match &unpack {
-            Unpack::Packet(Packet { data }) => data.clone(),
-            Unpack::QuickAck(_) => todo!(),
-        };
+    /// Send the functions again in order, with whether each one is a retry.
+    fn resend_all(&mut self, mut msg_ids: VecDeque<(MsgId, bool)>) {
+        while let Some((msg_id, retry)) = msg_ids.pop_front() {
+            let Some(mut request) = self.pending.remove(&msg_id) else {
+                continue;
+            };
 
-        let mut buffer = self.reader.buffer().split();
+            if retry {
+                if request.retries == 0 {
+                    self.resent.remove(&request.msg_id);
+                    let _ = request.sender.send(Err(InvokeError::Retries));
+                    continue;
+                }
+
+                request.retries -= 1;
+            }
+            request.container = None;
+            request.received = false;
 
-        let buf = &mut buffer[data];
+            let new_msg_id = self.msg_ids.get_using_system_time();
 
-        let encrypted = match Message::unpack(buf) {
-            Message::Plain(message) => return Err(SenderError::PlainMessage(message)),
-            Message::Encrypted(message) => message,
+            self.resent.insert(request.msg_id, new_msg_id);
+            self.push_request(new_msg_id, request);
+
+            self.move_followers(msg_id, Some(new_msg_id), &mut msg_ids);
+        }
+    }
+
+    /// Make the functions to be executed after `msg_id` reference `after`
+    /// instead, adding them to the functions to be sent again.
+    fn move_followers(
+        &mut self,
+        msg_id: MsgId,
+        after: Option<MsgId>,
+        msg_ids: &mut VecDeque<(MsgId, bool)>,
+    ) {
+        let mut followers: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, request)| request.after == Some(msg_id))
+            .map(|(&msg_id, _)| msg_id)
+            .collect();
+
+        followers.sort_unstable();
+
+        for follower in followers {
+            // Functions rejected along with the previous one are resent anyway.
+            if !msg_ids.iter().any(|&(msg_id, _)| msg_id == follower) {
+                // The old copy, if already sent, must not be executed.
+                let Some(request) = self.withdraw(follower) else {
+                    continue;
+                };
+
+                self.pending.insert(follower, request);
+                msg_ids.push_back((follower, false));
+            }
+
+            if let Some(request) = self.pending.get_mut(&follower) {
+                request.set_after(after);
+            }
+        }
+    }
+
+    /// Acknowledge again the messages acknowledged in the rejected container.
+    fn resend_acks(&mut self, container: MsgId) {
+        let Some(pos) = self.sent_acks.iter().position(|&(id, _)| id == container) else {
+            return;
         };
 
-        let auth_key_id = encrypted.auth_key_id.get();
+        let (_, acks) = self.sent_acks.remove(pos).unwrap();
 
-        if auth_key_id != i64::from_le_bytes(*self.auth_key.id()) {
-            return Err(SenderError::UnexpectedAuthKeyId(auth_key_id));
+        for msg_id in acks {
+            self.ack(msg_id);
         }
+    }
 
-        let buf = &mut buf[EncryptedMessage::HEADER_LEN..];
+    /// Fail the rejected function, or all functions of the rejected container.
+    fn fail(&mut self, msg_id: MsgId, err: impl Fn() -> InvokeError) {
+        self.resend_acks(msg_id);
 
-        let DecryptedMessage { salt, session_id } = encrypted.decrypt(&self.auth_key, buf)?;
+        let msg_ids = self
+            .containers
+            .remove(&msg_id)
+            .unwrap_or_else(|| vec![msg_id]);
 
-        // assert_eq!(decrypted.salt, self.salt);
+        for msg_id in msg_ids {
+            if let Some(request) = self.pending.remove(&msg_id) {
+                self.resent.remove(&request.msg_id);
+                let _ = request.sender.send(Err(err()));
+            }
+        }
+    }
 
-        if session_id != self.session_id {
-            return Err(SenderError::UnexpectedSessionId(session_id));
+    /// Cancel the functions whose [`Response`]s were dropped.
+    fn poll_cancelled(&mut self, cx: &mut Context<'_>) {
+        let mut msg_ids = Vec::new();
+
+        while let Poll::Ready(Some(msg_id)) = self.cancelled.poll_recv(cx) {
+            msg_ids.push(self.resent.get(&msg_id).copied().unwrap_or(msg_id));
         }
 
-        // Ok(SenderIter {
-        //     sender: self,
-        //     buffer,
-        //     pos: 0,
-        // })
+        // The following functions are sent again before the previous
+        // ones are cancelled.
+        msg_ids.sort_unstable_by(|a, b| b.cmp(a));
 
-        Ok(())
+        for msg_id in msg_ids {
+            let _ = self.cancel(msg_id);
+        }
+
+        // The answer is not waited for anymore, whether it was not generated,
+        // dropped while running, or already sent.
+        self.drop_answers
+            .retain_mut(|response| Pin::new(response).poll(cx).is_pending());
     }
 
-    pub fn poll<'a>(&'a mut self, cx: &mut Context<'_>) -> Poll<Result<(), SenderError>> {
-        if self.writer.is_empty() && !self.container.is_empty() {
-            let container = Self::new_container();
+    /// Withdraw the function, with the following ones executed after the
+    /// function it was to be executed after.
+    #[must_use]
+    fn cancel(&mut self, msg_id: MsgId) -> Option<Request> {
+        let request = self.withdraw(msg_id)?;
+        self.resent.remove(&request.msg_id);
+
+        let mut msg_ids = VecDeque::new();
+        self.move_followers(msg_id, request.after, &mut msg_ids);
+        self.resend_all(msg_ids);
+
+        Some(request)
+    }
 
-            let container = mem::replace(&mut self.container, container);
+    /// Remove the function if not sent yet, or ask the server to drop the answer.
+    #[must_use]
+    fn withdraw(&mut self, msg_id: MsgId) -> Option<Request> {
+        let request = self.pending.remove(&msg_id)?;
 
-            self.queue(container);
+        if self.container.remove(msg_id) {
+            return Some(request);
         }
 
-        loop {
-            match self.writer.poll(cx) {
-                Poll::Ready(Ok(_buffer)) => {}
-                Poll::Ready(Err(err)) => return Poll::Ready(Err(SenderError::Writer(err))),
-                Poll::Pending => break,
-            }
+        if let Some(container) = request.container
+            && let Some(msg_ids) = self.containers.get_mut(&container)
+        {
+            msg_ids.retain(|&id| id != msg_id);
+        }
+
+        self.drop_answer(msg_id);
+
+        Some(request)
+    }
+
+    /// Ask the server not to send the answer to the function.
+    fn drop_answer(&mut self, msg_id: MsgId) {
+        let func = tl::mtproto::funcs::RpcDropAnswer { req_msg_id: msg_id };
+
+        let response =
+            self.invoke_internal(tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)));
+
+        self.drop_answers.push(response);
+    }
+
+    /// Wake up to fail the functions by the deadline.
+    fn set_deadline(&mut self, deadline: Instant) {
+        match &mut self.timeout_timer {
+            Some(timer) if timer.deadline() <= deadline => {}
+            Some(timer) => timer.as_mut().reset(deadline),
+            None => self.timeout_timer = Some(Box::pin(time::sleep_until(deadline))),
         }
+    }
 
+    /// Fail the functions not answered by the deadline.
+    fn poll_timeout_timer(&mut self, cx: &mut Context<'_>) {
         loop {
-            let unpack = match self.reader.poll(cx) {
-                Poll::Ready(ControlFlow::Continue(Ok(unpack))) => unpack,
-                Poll::Ready(ControlFlow::Continue(Err(err))) => {
-                    return Poll::Ready(Err(SenderError::Reader(err)));
+            let Some(timer) = &mut self.timeout_timer else {
+                return;
+            };
+
+            if timer.as_mut().poll(cx).is_pending() {
+                return;
+            }
+
+            self.timeout_timer = None;
+
+            let now = Instant::now();
+
+            let mut msg_ids: Vec<_> = self
+                .pending
+                .iter()
+                .filter(|(_, request)| request.deadline.is_some_and(|deadline| deadline <= now))
+                .map(|(&msg_id, _)| msg_id)
+                .collect();
+
+            msg_ids.sort_unstable_by(|a, b| b.cmp(a));
+
+            for msg_id in msg_ids {
+                if let Some(request) = self.cancel(msg_id) {
+                    let _ = request
+                        .sender
+                        .send(Err(InvokeError::Sender(SenderError::Timeout)));
                 }
-                Poll::Ready(ControlFlow::Break(len)) => {
-                    let buf = self.reader.buffer();
-                    buf.reserve(len - buf.capacity());
-                    continue;
+            }
+
+            let next = self
+                .pending
+                .values()
+                .filter_map(|request| request.deadline)
+                .min();
+
+            if let Some(deadline) = next {
+                self.set_deadline(deadline);
+            }
+        }
+    }
+
+    /// Continue over a new connection after the previous one was lost,
+    /// sending the functions waiting for the response again.
+    ///
+    /// The functions the server acknowledged are still answered in the
+    /// session, so only their state is requested.
+    pub fn reconnect(&mut self, reader: Reader<R, T>, writer: QueuedWriter<W, T>) {
+        self.reader = reader;
+        self.writer = writer;
+
+        // The ping was meant for the lost connection, its answer does not matter.
+        if let Some((response, _)) = self.ping.take() {
+            let msg_id = self.resent.remove(&response.msg_id());
+
+            self.pending.remove(&msg_id.unwrap_or(response.msg_id()));
+        }
+
+        let mut cancelled: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, request)| request.sender.is_closed())
+            .map(|(&msg_id, _)| msg_id)
+            .collect();
+
+        cancelled.sort_unstable();
+
+        // The cancelled functions are not sent again, and the answers
+        // to the ones already sent are dropped once reconnected.
+        cancelled.retain(|&msg_id| {
+            let request = self.pending.remove(&msg_id).unwrap();
+            self.resent.remove(&request.msg_id);
+
+            for follower in self.pending.values_mut() {
+                if follower.after == Some(msg_id) {
+                    follower.set_after(request.after);
                 }
-                Poll::Pending => break,
-            };
+            }
+
+            !self.container.remove(msg_id)
+        });
+
+        // Whatever was not sent yet is lost along with the connection.
+        self.container = Self::new_container(&self.config);
+        self.flush_timer = None;
+        self.quick_acks.clear();
+        self.containers.clear();
+        self.sent_acks.clear();
+        self.state_req = None;
+
+        // Count the intervals from the new connection. The flood backoff is
+        // kept until a message is received over it.
+        self.ping_timer = None;
+        self.state_timer = None;
+
+        self.resend_pending();
 
-            return Poll::Ready(self.unpack(dbg!(unpack)));
+        let received: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, request)| request.received)
+            .map(|(&msg_id, _)| msg_id)
+            .collect();
+
+        if !received.is_empty() {
+            self.request_state(received);
         }
 
-        Poll::Pending
+        for msg_id in cancelled {
+            self.drop_answer(msg_id);
+        }
+    }
+
+    /// Continue in a new session, e.g. after the server rejected the sequence
+    /// numbers, sending the functions waiting for the response again.
+    ///
+    /// ---
+    /// https://core.telegram.org/mtproto/service_messages_about_messages#notice-of-ignored-error-message
+    fn new_session(&mut self) {
+        let mut session_id = [0; 8];
+        getrandom::fill(&mut session_id).unwrap();
+
+        self.old_session_id = Some(mem::replace(
+            &mut self.session_id,
+            Session::from_le_bytes(session_id),
+        ));
+        self.seq_nos = SeqNos::new();
+
+        // Messages not sent yet are numbered for the previous session.
+        self.container = Self::new_container(&self.config);
+        self.flush_timer = None;
+        self.containers.clear();
+        self.sent_acks.clear();
+        self.state_req = None;
+
+        // Functions received in the previous session are not answered in this one.
+        for request in self.pending.values_mut() {
+            request.received = false;
+        }
+
+        self.resend_pending();
+    }
+
+    /// Send all functions waiting for the response, except the ones
+    /// received by the server, again in order.
+    fn resend_pending(&mut self) {
+        let mut msg_ids: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, request)| !request.received)
+            .map(|(&msg_id, _)| msg_id)
+            .collect();
+
+        msg_ids.sort_unstable();
+
+        for msg_id in msg_ids {
+            self.resend(msg_id, true);
+        }
+    }
+
+    /// Ask the server to delay the response to the next request until
+    /// there are messages to be sent, for up to `max_wait` milliseconds.
+    ///
+    /// Only meaningful with the [`Http`] transport, where the server
+    /// cannot send messages without a pending request. It is never sent
+    /// automatically: to receive the messages initiated by the server, call
+    /// this again whenever a packet is received, so that one is always pending.
+    ///
+    /// [`Http`]: crate::transport::Http
+    pub fn http_wait(&mut self, http_wait: &tl::mtproto::types::HttpWait) {
+        let msg = Msg {
+            msg_id: self.msg_ids.get_using_system_time(),
+            seq_no: self.seq_nos.non_content_related(),
+        };
+
+        self.push_message(msg, http_wait);
+    }
+
+    /// Send a content-related service message, which has no response.
+    fn push_service<X: tl::ser::SerializeUnchecked + tl::Identifiable>(&mut self, x: &X) -> MsgId {
+        let msg_id = self.msg_ids.get_using_system_time();
+
+        let msg = Msg {
+            msg_id,
+            seq_no: self.seq_nos.get_content_related(),
+        };
+
+        self.push_message(msg, x);
+
+        msg_id
+    }
+
+    /// Push the service message into the container,
+    /// or send it on its own if too large.
+    fn push_message<X: tl::ser::SerializeUnchecked + tl::Identifiable>(&mut self, msg: Msg, x: &X) {
+        let x = tl::CalculatedLen::new(tl::ConstructorId::from_ref(x));
+
+        if self.container.fits(x.len()) {
+            self.get_container(x.len()).push(msg, x, false);
+        } else {
+            let mut body = Vec::with_capacity(x.len());
+            body.ser(&*x);
+
+            self.queue_standalone(msg, &body, false);
+        }
+    }
+
+    /// Acknowledge the receipt of the content-related message.
+    fn ack(&mut self, msg_id: MsgId) {
+        self.acks.push(msg_id);
+
+        if self.acks.len() >= self.config.max_pending_acks {
+            self.flush_acks();
+        } else if self.ack_timer.is_none() {
+            self.ack_timer = Some(Box::pin(time::sleep(self.config.ack_delay)));
+        }
+    }
+
+    /// Queue the containers to send the pending acknowledgements.
+    fn flush_acks(&mut self) {
+        // The container may be too full to take all of them.
+        while !self.acks.is_empty() {
+            self.flush();
+        }
+    }
+
+    /// Queue the current container, replacing it with an empty one.
+    fn flush(&mut self) {
+        let container = Self::new_container(&self.config);
+
+        let container = mem::replace(&mut self.container, container);
+
+        self.flush_timer = None;
+
+        self.queue(container);
+    }
+
+    /// Returns `true` once the current container is due to be sent.
+    fn poll_flush_timer(&mut self, cx: &mut Context<'_>) -> bool {
+        if self.config.flush_delay.is_zero() {
+            return true;
+        }
+
+        let timer = self
+            .flush_timer
+            .get_or_insert_with(|| Box::pin(time::sleep(self.config.flush_delay)));
+
+        timer.as_mut().poll(cx).is_ready()
+    }
+
+    /// Returns `true` once the pending acknowledgements are due.
+    fn poll_ack_timer(&mut self, cx: &mut Context<'_>) -> bool {
+        let Some(timer) = &mut self.ack_timer else {
+            return false;
+        };
+
+        if timer.as_mut().poll(cx).is_pending() {
+            return false;
+        }
+
+        self.ack_timer = None;
+
+        true
+    }
+
+    /// Process the `get_future_salts` response,
+    /// or request more salts if the known ones are about to run out.
+    fn poll_future_salts(&mut self, cx: &mut Context<'_>) {
+        let Some(num) = self.config.future_salts else {
+            return;
+        };
+
+        let now = self.msg_ids.server_time();
+
+        if let Some(response) = &mut self.future_salts {
+            let Poll::Ready(result) = Pin::new(response).poll(cx) else {
+                return;
+            };
+
+            self.future_salts = None;
+
+            match result {
+                Ok(tl::mtproto::enums::FutureSalts::FutureSalts(x)) => self.salts.set(x.salts.0),
+                Err(_) => self.future_salts_retry = now + FUTURE_SALTS_RETRY_DELAY,
+            }
+        }
+
+        if now >= self.future_salts_retry && self.salts.needs_refill(now) {
+            let func = tl::mtproto::funcs::GetFutureSalts { num };
+
+            let mut response =
+                self.invoke_internal(tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)));
+
+            // Register the waker.
+            let _ = Pin::new(&mut response).poll(cx);
+
+            self.future_salts = Some(response);
+        }
+    }
+
+    /// Request the state of the functions waiting for the response for too long.
+    fn poll_state_timer(&mut self, cx: &mut Context<'_>) {
+        let Some(delay) = self.config.state_req_delay else {
+            return;
+        };
+
+        loop {
+            let timer = self
+                .state_timer
+                .get_or_insert_with(|| Box::pin(time::sleep(delay)));
+
+            if timer.as_mut().poll(cx).is_pending() {
+                return;
+            }
+
+            self.state_timer = None;
+
+            let now = Instant::now();
+
+            let msg_ids: Vec<_> = self
+                .pending
+                .iter()
+                .filter(|(_, request)| now - request.sent >= delay)
+                .map(|(&msg_id, _)| msg_id)
+                .collect();
+
+            if !msg_ids.is_empty() {
+                self.request_state(msg_ids);
+            }
+        }
+    }
+
+    /// Ask the server about the state of the functions, to resend the ones
+    /// it has not received.
+    fn request_state(&mut self, mut msg_ids: Vec<MsgId>) {
+        let now = Instant::now();
+
+        for msg_id in &msg_ids {
+            if let Some(request) = self.pending.get_mut(msg_id) {
+                request.sent = now;
+            }
+        }
+
+        msg_ids.sort_unstable();
+
+        let msgs_state_req = tl::mtproto::types::MsgsStateReq {
+            msg_ids: msg_ids.clone(),
+        };
+
+        // An unanswered request is superseded.
+        self.state_req = Some((self.push_service(&msgs_state_req), msg_ids));
+    }
+
+    /// Send keepalive pings and track the `pong`s.
+    fn poll_keepalive(&mut self, cx: &mut Context<'_>) -> Result<(), SenderError> {
+        let Some(interval) = self.config.ping_interval else {
+            return Ok(());
+        };
+
+        if let Some((response, sent)) = &mut self.ping
+            && let Poll::Ready(result) = Pin::new(response).poll(cx)
+        {
+            let rtt = sent.elapsed();
+
+            self.ping = None;
+            self.ping_timer = None;
+
+            if result.is_ok() {
+                self.rtt = Some(rtt);
+                self.events.push_back(SenderEvent::Pong { rtt });
+            }
+        }
+
+        loop {
+            let timer = self
+                .ping_timer
+                .get_or_insert_with(|| Box::pin(time::sleep(interval)));
+
+            if timer.as_mut().poll(cx).is_pending() {
+                return Ok(());
+            }
+
+            if self.ping.is_some() {
+                return Err(SenderError::PingTimeout);
+            }
+
+            let timeout = self.config.ping_timeout;
+
+            let func = tl::mtproto::funcs::PingDelayDisconnect {
+                ping_id: self.msg_ids.last(),
+                disconnect_delay: (interval + timeout).as_secs_f64().ceil() as i32,
+            };
+
+            let mut response =
+                self.invoke_internal(tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)));
+
+            // Register the waker.
+            let _ = Pin::new(&mut response).poll(cx);
+
+            self.ping = Some((response, Instant::now()));
+            self.ping_timer = Some(Box::pin(time::sleep(timeout)));
+        }
+    }
+
+    fn queue(&mut self, mut container: Container<T>) {
+        // Piggy-back the pending acknowledgements.
+        let mut acks = None;
+
+        // As many as fit, the others are sent in the next container.
+        let len = container
+            .spare_capacity()
+            .map_or(0, |len| len.saturating_sub(MSGS_ACK_HEADER_LEN) / 8)
+            .min(self.acks.len());
+
+        if len > 0 {
+            let msgs_ack = tl::mtproto::types::MsgsAck {
+                msg_ids: self.acks.drain(..len).collect(),
+            };
+
+            let x = tl::CalculatedLen::new(tl::ConstructorId::from_ref(&msgs_ack));
+
+            let msg = Msg {
+                msg_id: self.msg_ids.get_using_system_time(),
+                seq_no: self.seq_nos.non_content_related(),
+            };
+
+            container.push(msg, x, false);
+
+            if self.acks.is_empty() {
+                self.ack_timer = None;
+            }
+
+            acks = Some(msgs_ack.msg_ids);
+        }
+
+        let message = self.decrypted_message();
+
+        let msg = Msg {
+            msg_id: self.msg_ids.get_using_system_time(),
+            seq_no: self.seq_nos.non_content_related(),
+        };
+
+        if let Some(acks) = acks {
+            // Rejections arrive soon after, the older acknowledgements are forgotten.
+            let min_time = self.msg_ids.server_time() - MAX_MSG_AGE;
+
+            while self
+                .sent_acks
+                .front()
+                .is_some_and(|&(container, _)| ((container >> 32) as i32) < min_time)
+            {
+                self.sent_acks.pop_front();
+            }
+
+            self.sent_acks.push_back((msg.msg_id, acks));
+        }
+
+        let (transport, mtp, buffer, quick_ack, msg_ids) = container.finalize();
+
+        let requests: Vec<_> = msg_ids
+            .into_iter()
+            .filter(|msg_id| match self.pending.get_mut(msg_id) {
+                Some(request) => {
+                    request.container = Some(msg.msg_id);
+                    true
+                }
+                None => false,
+            })
+            .collect();
+
+        if !requests.is_empty() {
+            self.containers.insert(msg.msg_id, requests);
+        }
+
+        let (h, f, token) = self.writer.queue(
+            transport,
+            mtp,
+            buffer,
+            &self.auth_key,
+            message,
+            msg,
+            !quick_ack.is_empty(),
+        );
+
+        if let Some(token) = token {
+            self.quick_acks.insert(token, quick_ack);
+        }
+
+        if let Some(_h) = h {}
+
+        if let Some(_f) = f {}
+    }
+
+    /// Send the message on its own, as it is too large for a container.
+    fn queue_standalone(&mut self, msg: Msg, body: &[u8], quick_ack: bool) {
+        // Keep the order of the messages.
+        if !self.container.is_empty() {
+            self.flush();
+        }
+
+        let mut buffer = BytesMut::with_capacity(
+            T::HEADER
+                + T::FOOTER
+                + EncryptedEnvelopeSize::HEADER
+                + EncryptedEnvelopeSize::FOOTER
+                + body.len(),
+        );
+
+        let transport = Envelope::split(&mut buffer);
+        let mtp = Envelope::split(&mut buffer);
+
+        buffer.extend_from_slice(body);
+
+        let message = self.decrypted_message();
+        let msg_id = msg.msg_id;
+
+        let (h, f, token) = self.writer.queue(
+            transport,
+            mtp,
+            buffer,
+            &self.auth_key,
+            message,
+            msg,
+            quick_ack,
+        );
+
+        if let Some(token) = token {
+            self.quick_acks.insert(token, vec![msg_id]);
+        }
+
+        if let Some(_h) = h {}
+
+        if let Some(_f) = f {}
+    }
+
+    fn decrypted_message(&mut self) -> DecryptedMessage {
+        if let Some(salt) = self.salts.get(self.msg_ids.server_time()) {
+            self.salt = salt;
+        }
+
+        DecryptedMessage {
+            salt: self.salt,
+            session_id: self.session_id,
+        }
+    }
+
+    fn unpack(&mut self, unpack: Unpack) -> Result<(), SenderError> {
+        let data = match unpack {
+            Unpack::Packet(Packet { data }) => data,
+            Unpack::QuickAck(QuickAck { token, .. }) => {
+                self.reader.buffer().clear();
+
+                // Unknown tokens are ignored: quick acks are only a hint.
+                if let Some(msg_ids) = self.quick_acks.remove(&token) {
+                    self.events.push_back(SenderEvent::QuickAck(msg_ids));
+                }
+
+                return Ok(());
+            }
+        };
+
+        let mut buffer = self.reader.buffer().split();
+
+        let buf = &mut buffer[data.clone()];
+
+        let encrypted = match Message::unpack(buf) {
+            Message::Plain(message) => return Err(SenderError::PlainMessage(message)),
+            Message::Encrypted(message) => message,
+        };
+
+        let auth_key_id = encrypted.auth_key_id.get();
+
+        if auth_key_id != i64::from_le_bytes(*self.auth_key.id()) {
+            return Err(SenderError::UnexpectedAuthKeyId(auth_key_id));
+        }
+
+        let buf = &mut buf[EncryptedMessage::HEADER_LEN..];
+
+        // The salt of incoming messages is not checked: only the server validates salts.
+        let DecryptedMessage { session_id, .. } = encrypted.decrypt(&self.auth_key, buf)?;
+
+        if session_id != self.session_id {
+            // Functions sent in the previous session were sent again.
+            if Some(session_id) == self.old_session_id {
+                return Ok(());
+            }
+
+            return Err(SenderError::UnexpectedSessionId(session_id));
+        }
+
+        self.flood_backoff = MIN_FLOOD_BACKOFF;
+
+        let start = data.start + EncryptedMessage::HEADER_LEN + DecryptedMessage::HEADER_LEN;
+
+        let msg = Msg {
+            msg_id: i64::from_le_bytes(buffer[start..start + 8].try_into().unwrap()),
+            seq_no: i32::from_le_bytes(buffer[start + 8..start + 12].try_into().unwrap()),
+        };
+
+        let len = i32::from_le_bytes(buffer[start + 12..start + 16].try_into().unwrap());
+
+        let start = start + Msg::HEADER_LEN;
+
+        if len < 4 || len % 4 != 0 || start + len as usize > data.end {
+            return Err(SenderError::InvalidMsgLength(len));
+        }
+
+        let padding_len = data.end - (start + len as usize);
+
+        if !(MIN_PADDING_LEN..=MAX_PADDING_LEN).contains(&padding_len) {
+            return Err(SenderError::InvalidMsgLength(len));
+        }
+
+        let mut body = buffer.split_off(start);
+        body.truncate(len as usize);
+
+        self.handle(msg, body)
+    }
+
+    /// Handle a message received from the server, be it standalone or in a container.
+    fn handle(&mut self, msg: Msg, mut body: BytesMut) -> Result<(), SenderError> {
+        // Identifiers of the messages sent by the server are odd.
+        if msg.msg_id & 3 != 1 && msg.msg_id & 3 != 3 {
+            return Err(SenderError::InvalidMsgId(msg.msg_id));
+        }
+
+        // Replayed messages are ignored.
+        if self.replay_window.contains(msg.msg_id) {
+            return Ok(());
+        }
+
+        let server_time = self.msg_ids.server_time();
+        let msg_time = (msg.msg_id >> 32) as i32;
+
+        // Except the notifications that the local clock is off,
+        // which the time offset is corrected from.
+        let is_bad_msg_notification =
+            body.starts_with(&tl::mtproto::types::BadMsgNotification::CONSTRUCTOR_ID.to_le_bytes());
+
+        if (msg_time < server_time - MAX_MSG_AGE || msg_time > server_time + MAX_MSG_AHEAD)
+            && !is_bad_msg_notification
+        {
+            return Ok(());
+        }
+
+        // Responses are created right before being sent,
+        // so their identifiers reflect the current server time.
+        if msg.msg_id & 3 == 1 {
+            self.msg_ids.correct(msg.msg_id);
+        }
+
+        self.replay_window.insert(msg.msg_id);
+
+        if msg.seq_no & 1 == 1 {
+            self.ack(msg.msg_id);
+        }
+
+        if GzipPacked::is_packed(&body) {
+            body = Self::gzip_unpack(&body)?;
+        }
+
+        let Some(&constructor_id) = body.first_chunk() else {
+            return Err(SenderError::InvalidMsgLength(body.len() as i32));
+        };
+
+        match u32::from_le_bytes(constructor_id) {
+            pack::MsgContainer::CONSTRUCTOR_ID => self.handle_container(msg, body),
+            RPC_RESULT if body.len() >= 12 => {
+                let req_msg_id = i64::from_le_bytes(body[4..12].try_into().unwrap());
+
+                self.rpc_result(req_msg_id, body.split_off(12))
+            }
+            tl::mtproto::types::BadMsgNotification::CONSTRUCTOR_ID
+            | tl::mtproto::types::BadServerSalt::CONSTRUCTOR_ID => {
+                self.bad_msg_notification(msg, tl::de(&body)?);
+
+                Ok(())
+            }
+            tl::mtproto::types::MsgsStateReq::CONSTRUCTOR_ID => {
+                self.msgs_state_req(msg, tl::de(&body)?);
+
+                Ok(())
+            }
+            tl::mtproto::types::MsgsStateInfo::CONSTRUCTOR_ID => {
+                self.msgs_state_info(tl::de(&body)?);
+
+                Ok(())
+            }
+            tl::mtproto::types::MsgsAck::CONSTRUCTOR_ID => {
+                let tl::mtproto::enums::MsgsAck::MsgsAck(x) = tl::de(&body)?;
+
+                for msg_id in x.msg_ids {
+                    self.received(msg_id);
+                }
+
+                Ok(())
+            }
+            tl::mtproto::types::MsgResendReq::CONSTRUCTOR_ID => {
+                let tl::mtproto::enums::MsgResendReq::MsgResendReq(x) = tl::de(&body)?;
+
+                for msg_id in x.msg_ids {
+                    self.resend(msg_id, false);
+                }
+
+                Ok(())
+            }
+            tl::mtproto::types::MsgDetailedInfo::CONSTRUCTOR_ID
+            | tl::mtproto::types::MsgNewDetailedInfo::CONSTRUCTOR_ID => {
+                self.msg_detailed_info(tl::de(&body)?);
+
+                Ok(())
+            }
+            tl::mtproto::types::NewSessionCreated::CONSTRUCTOR_ID => {
+                self.new_session_created(tl::de(&body)?);
+
+                Ok(())
+            }
+            // Sent as standalone messages instead of `rpc_result`.
+            tl::mtproto::types::Pong::CONSTRUCTOR_ID
+            | tl::mtproto::types::FutureSalts::CONSTRUCTOR_ID
+                if body.len() >= 12 =>
+            {
+                let msg_id = i64::from_le_bytes(body[4..12].try_into().unwrap());
+
+                self.rpc_result(msg_id, body)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Forget the quick ack expected for the message, as the server
+    /// has received it, e.g. it was acknowledged or answered.
+    fn received(&mut self, msg_id: MsgId) {
+        self.quick_acks
+            .retain(|_, msg_ids| !msg_ids.contains(&msg_id));
+
+        // Either the container, or a function sent in it.
+        let container = match self.pending.get_mut(&msg_id) {
+            Some(request) => {
+                request.received = true;
+                request.container
+            }
+            None => Some(msg_id),
+        };
+
+        if let Some(container) = container {
+            self.accepted(container);
+        }
+    }
+
+    /// Forget the container accepted as a whole, which is not rejected anymore.
+    fn accepted(&mut self, container: MsgId) {
+        if let Some(msg_ids) = self.containers.remove(&container) {
+            for msg_id in msg_ids {
+                if let Some(request) = self.pending.get_mut(&msg_id) {
+                    request.container = None;
+                    request.received = true;
+                }
+            }
+        }
+
+        self.sent_acks.retain(|&(id, _)| id != container);
+    }
+
+    /// Handle each message of the container in order.
+    fn handle_container(&mut self, container: Msg, mut body: BytesMut) -> Result<(), SenderError> {
+        let mut messages = Vec::new();
+
+        for message in unpack::MsgContainer::new(tl::de::Buf::new(&body[4..]))? {
+            let (msg, buf) = message?;
+
+            // The container is created after the messages it contains.
+            if msg.msg_id >= container.msg_id {
+                return Err(SenderError::InvalidMsgId(msg.msg_id));
+            }
+
+            let start = buf.as_slice().as_ptr() as usize - body.as_ptr() as usize;
+
+            messages.push((msg, start..start + buf.len()));
+        }
+
+        let mut offset = 0;
+
+        for (msg, range) in messages {
+            body.advance(range.start - offset);
+
+            let message = body.split_to(range.len());
+
+            offset = range.end;
+
+            self.handle(msg, message)?;
+        }
+
+        Ok(())
+    }
+
+    /// Inflate the serialized `gzip_packed` object.
+    fn gzip_unpack(buf: &[u8]) -> Result<BytesMut, SenderError> {
+        let gzip_packed: GzipPacked = tl::de(&buf[4..])?;
+
+        gzip_packed.unpack().map_err(SenderError::GzipPacked)
+    }
+
+    /// Recover from the rejection of a sent message, resending it if possible.
+    ///
+    /// ---
+    /// https://core.telegram.org/mtproto/service_messages_about_messages#notice-of-ignored-error-message
+    fn bad_msg_notification(
+        &mut self,
+        msg: Msg,
+        notification: tl::mtproto::enums::BadMsgNotification,
+    ) {
+        use tl::mtproto::enums::BadMsgNotification::*;
+
+        let x = match notification {
+            BadServerSalt(x) => {
+                // The server knows better.
+                self.salts.clear();
+                self.salt = x.new_server_salt;
+
+                return self.resend(x.bad_msg_id, false);
+            }
+            BadMsgNotification(x) => x,
+        };
+
+        match x.error_code {
+            // msg_id too low or too high: the local clock is off.
+            16 | 17 => self.msg_ids.set_server_time((msg.msg_id >> 32) as i32),
+            // Too old to verify whether the server has received it.
+            20 => {}
+            // msg_seqno too low or too high: the session cannot be recovered.
+            32 | 33 => return self.new_session(),
+            code => {
+                return self.fail(x.bad_msg_id, || InvokeError::BadMsgNotification { code });
+            }
+        }
+
+        self.resend(x.bad_msg_id, true);
+    }
+
+    /// Report the state of the messages received from the server.
+    fn msgs_state_req(&mut self, msg: Msg, req: tl::mtproto::enums::MsgsStateReq) {
+        let tl::mtproto::enums::MsgsStateReq::MsgsStateReq(x) = req;
+
+        let info = x
+            .msg_ids
+            .into_iter()
+            .map(|msg_id| match self.replay_window.state(msg_id) {
+                // Received, and already acknowledged unless still pending.
+                4 if !self.acks.contains(&msg_id) => 4 | 8,
+                state => state,
+            })
+            .collect();
+
+        self.push_service(&tl::mtproto::types::MsgsStateInfo {
+            req_msg_id: msg.msg_id,
+            info,
+        });
+    }
+
+    /// Resend the queried functions the server has not received.
+    fn msgs_state_info(&mut self, info: tl::mtproto::enums::MsgsStateInfo) {
+        let tl::mtproto::enums::MsgsStateInfo::MsgsStateInfo(x) = info;
+
+        let Some((_, msg_ids)) = self
+            .state_req
+            .take_if(|(req_msg_id, _)| *req_msg_id == x.req_msg_id)
+        else {
+            return;
+        };
+
+        for (msg_id, state) in msg_ids.into_iter().zip(x.info) {
+            // Received functions are still being processed.
+            if state & 7 != 4 {
+                self.resend(msg_id, false);
+            }
+        }
+    }
+
+    /// Acknowledge the answer if already received, or request it again.
+    fn msg_detailed_info(&mut self, info: tl::mtproto::enums::MsgDetailedInfo) {
+        use tl::mtproto::enums::MsgDetailedInfo::*;
+
+        let answer_msg_id = match info {
+            MsgDetailedInfo(x) => x.answer_msg_id,
+            MsgNewDetailedInfo(x) => x.answer_msg_id,
+        };
+
+        if self.replay_window.state(answer_msg_id) == 4 {
+            self.ack(answer_msg_id);
+        } else {
+            self.push_service(&tl::mtproto::types::MsgResendReq {
+                msg_ids: vec![answer_msg_id],
+            });
+        }
+    }
+
+    fn new_session_created(&mut self, notification: tl::mtproto::enums::NewSession) {
+        let tl::mtproto::enums::NewSession::NewSessionCreated(x) = notification;
+
+        self.salts.clear();
+        self.salt = x.server_salt;
+
+        self.events.push_back(SenderEvent::UpdatesGap);
+
+        // Messages sent before the session was created were not received by it.
+        self.containers
+            .retain(|&container, _| container >= x.first_msg_id);
+
+        let mut msg_ids: Vec<_> = self
+            .pending
+            .keys()
+            .copied()
+            .filter(|&msg_id| msg_id < x.first_msg_id)
+            .collect();
+
+        msg_ids.sort_unstable();
+
+        for msg_id in msg_ids {
+            self.resend(msg_id, false);
+        }
+    }
+
+    fn rpc_result(&mut self, req_msg_id: MsgId, mut result: BytesMut) -> Result<(), SenderError> {
+        self.received(req_msg_id);
+
+        // The response may have been already received or cancelled.
+        let Some(request) = self.pending.remove(&req_msg_id) else {
+            return Ok(());
+        };
+
+        self.resent.remove(&request.msg_id);
+
+        if GzipPacked::is_packed(&result) {
+            result = Self::gzip_unpack(&result)?;
+        }
+
+        let is_rpc_error =
+            result.starts_with(&tl::mtproto::types::RpcError::CONSTRUCTOR_ID.to_le_bytes());
+
+        let result = if is_rpc_error {
+            match tl::de(&result) {
+                Ok(tl::mtproto::enums::RpcError::RpcError(err)) => Err(InvokeError::Rpc(err)),
+                Err(source) => Err(InvokeError::Deserialization {
+                    source,
+                    buffer: result,
+                }),
+            }
+        } else {
+            Ok(result)
+        };
+
+        // The response handle may have been dropped.
+        let _ = request.sender.send(result);
+
+        Ok(())
+    }
+
+    /// Translate the documented transport status codes.
+    fn reader_error(&mut self, err: ReaderError) -> SenderError {
+        let ReaderError::Transport(TransportError::Status(status)) = err else {
+            return SenderError::Reader(err);
+        };
+
+        match status {
+            TransportStatus::AuthKeyNotFound => SenderError::AuthKeyNotFound,
+            TransportStatus::TransportFlood => {
+                let backoff = self.flood_backoff;
+
+                self.flood_backoff = (backoff * 2).min(MAX_FLOOD_BACKOFF);
+
+                SenderError::TransportFlood { backoff }
+            }
+            TransportStatus::InvalidDc => SenderError::InvalidDc,
+            TransportStatus::Other(_) => SenderError::Reader(err),
+        }
+    }
+
+    pub fn poll<'a>(&'a mut self, cx: &mut Context<'_>) -> Poll<Result<SenderEvent, SenderError>> {
+        if self.poll_ack_timer(cx) {
+            self.flush_acks();
+        }
+
+        self.poll_future_salts(cx);
+        self.poll_state_timer(cx);
+
+        if let Err(err) = self.poll_keepalive(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        self.poll_cancelled(cx);
+        self.poll_timeout_timer(cx);
+
+        if self.writer.is_empty() && !self.container.is_empty() && self.poll_flush_timer(cx) {
+            self.flush();
+        }
+
+        loop {
+            match self.writer.poll(cx) {
+                Poll::Ready(Ok(_buffer)) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(SenderError::Writer(err))),
+                Poll::Pending => break,
+            }
+        }
+
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Poll::Ready(Ok(event));
+            }
+
+            let unpack = match self.reader.poll(cx) {
+                Poll::Ready(ControlFlow::Continue(Ok(unpack))) => unpack,
+                Poll::Ready(ControlFlow::Continue(Err(err))) => {
+                    return Poll::Ready(Err(self.reader_error(err)));
+                }
+                Poll::Ready(ControlFlow::Break(len)) => {
+                    let buf = self.reader.buffer();
+                    buf.reserve(len - buf.capacity());
+                    continue;
+                }
+                Poll::Pending => break,
+            };
+
+            if let Err(err) = self.unpack(unpack) {
+                return Poll::Ready(Err(err));
+            }
+        }
+
+        // Register the timer started while reading, or flush right away if due.
+        if self.poll_ack_timer(cx) {
+            self.flush_acks();
+
+            cx.waker().wake_by_ref();
+        }
+
+        // Send the messages queued while reading once due, the timer wakes up otherwise.
+        if self.writer.is_empty() && !self.container.is_empty() && self.poll_flush_timer(cx) {
+            cx.waker().wake_by_ref();
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::poll_fn;
+    use std::io::Write;
+    use std::pin::pin;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf};
+
+    use crate::crypto;
+    use crate::mtproto::Side;
+    use crate::transport::Intermediate;
+
+    type TestSender = Sender<Intermediate, ReadHalf<DuplexStream>, WriteHalf<DuplexStream>>;
+
+    const AUTH_KEY: [u8; 256] = [7; 256];
+    const SALT: Salt = 0x1234;
+    const SESSION_ID: Session = 0x5678;
+
+    /// Returns a sender connected to the returned server stream,
+    /// without the background requests unless enabled by the test.
+    fn new_sender() -> (TestSender, DuplexStream) {
+        new_sender_with_config(SenderConfig {
+            future_salts: None,
+            ping_interval: None,
+            state_req_delay: None,
+            ..SenderConfig::default()
+        })
+    }
+
+    type TestConnection = (
+        Reader<ReadHalf<DuplexStream>, Intermediate>,
+        QueuedWriter<WriteHalf<DuplexStream>, Intermediate>,
+        DuplexStream,
+    );
+
+    fn connect() -> TestConnection {
+        let (client, server) = tokio::io::duplex(1024 * 1024);
+        let (r, w) = tokio::io::split(client);
+
+        let buffer = BytesMut::with_capacity(1024 * 1024);
+        let (reader, writer) = crate::init(Intermediate, r, buffer, w);
+
+        (reader, QueuedWriter::new(writer), server)
+    }
+
+    fn new_sender_with_config(config: SenderConfig) -> (TestSender, DuplexStream) {
+        let (reader, writer, server) = connect();
+
+        let auth_key = AuthKey::new(AUTH_KEY);
+        let sender = Sender::with_config(reader, writer, auth_key, SALT, 0, SESSION_ID, config);
+
+        (sender, server)
+    }
+
+    /// Encrypt the message as the server would, framed by [`Intermediate`].
+    fn server_packet(msg: Msg, body: &[u8]) -> Vec<u8> {
+        server_packet_in(SESSION_ID, msg, body)
+    }
+
+    /// Same as [`server_packet`], but in another session.
+    fn server_packet_in(session_id: Session, msg: Msg, body: &[u8]) -> Vec<u8> {
+        let auth_key = AuthKey::new(AUTH_KEY);
+
+        let mut plaintext = Vec::new();
+        plaintext.extend_from_slice(&SALT.to_le_bytes());
+        plaintext.extend_from_slice(&session_id.to_le_bytes());
+        plaintext.extend_from_slice(&msg.msg_id.to_le_bytes());
+        plaintext.extend_from_slice(&msg.seq_no.to_le_bytes());
+        plaintext.extend_from_slice(&(body.len() as i32).to_le_bytes());
+        plaintext.extend_from_slice(body);
+        plaintext.resize((plaintext.len() + 12 + 15) & !15, 0);
+
+        let msg_key = auth_key.compute_msg_key(&plaintext, Side::Server);
+        let (aes_key, mut aes_iv) = auth_key.compute_aes_params(&msg_key, Side::Server);
+
+        crypto::aes_ige_encrypt(&mut plaintext, &aes_key, &mut aes_iv);
+
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&((24 + plaintext.len()) as u32).to_le_bytes());
+        packet.extend_from_slice(auth_key.id());
+        packet.extend_from_slice(&msg_key);
+        packet.extend_from_slice(&plaintext);
+        packet
+    }
+
+    /// Read and decrypt the next message sent by the client.
+    async fn client_message(server: &mut DuplexStream) -> (Salt, Msg, Vec<u8>) {
+        let mut len = server.read_u32_le().await.unwrap();
+
+        if len == 0xeeeeeeee {
+            len = server.read_u32_le().await.unwrap();
+        }
+
+        // Without the quick ack bit.
+        let mut packet = vec![0; (len & 0x7fffffff) as usize];
+        server.read_exact(&mut packet).await.unwrap();
+
+        let auth_key = AuthKey::new(AUTH_KEY);
+
+        let (h, plaintext) = packet.split_at_mut(24);
+
+        let msg_key = h[8..24].try_into().unwrap();
+        let (aes_key, mut aes_iv) = auth_key.compute_aes_params(&msg_key, Side::Client);
+
+        crypto::aes_ige_decrypt(plaintext, &aes_key, &mut aes_iv);
+
+        let msg = Msg {
+            msg_id: i64::from_le_bytes(plaintext[16..24].try_into().unwrap()),
+            seq_no: i32::from_le_bytes(plaintext[24..28].try_into().unwrap()),
+        };
+
+        let len = i32::from_le_bytes(plaintext[28..32].try_into().unwrap()) as usize;
+
+        let salt = i64::from_le_bytes(plaintext[0..8].try_into().unwrap());
+
+        (salt, msg, plaintext[32..32 + len].to_vec())
+    }
+
+    fn container_messages(body: &[u8]) -> Vec<(Msg, Vec<u8>)> {
+        assert!(body.starts_with(&pack::MsgContainer::CONSTRUCTOR_ID.to_le_bytes()));
+
+        unpack::MsgContainer::new(tl::de::Buf::new(&body[4..]))
+            .unwrap()
+            .map(|message| {
+                let (msg, buf) = message.unwrap();
+
+                (msg, buf.as_slice().to_vec())
+            })
+            .collect()
+    }
+
+    /// Poll the sender once to write the queued messages.
+    async fn flush(sender: &mut TestSender) {
+        poll_fn(|cx| {
+            assert!(sender.poll(cx).is_pending());
+
+            Poll::Ready(())
+        })
+        .await
+    }
+
+    fn pong_body(msg_id: MsgId, ping_id: i64) -> Vec<u8> {
+        let mut body = tl::mtproto::types::Pong::CONSTRUCTOR_ID
+            .to_le_bytes()
+            .to_vec();
+        body.extend_from_slice(&msg_id.to_le_bytes());
+        body.extend_from_slice(&ping_id.to_le_bytes());
+        body
+    }
+
+    fn rpc_result(req_msg_id: MsgId, result: &[u8]) -> Vec<u8> {
+        let mut body = RPC_RESULT.to_le_bytes().to_vec();
+        body.extend_from_slice(&req_msg_id.to_le_bytes());
+        body.extend_from_slice(result);
+        body
+    }
+
+    fn bad_server_salt(msg: &Msg, new_server_salt: i64) -> Vec<u8> {
+        boxed(&tl::mtproto::types::BadServerSalt {
+            bad_msg_id: msg.msg_id,
+            bad_msg_seqno: msg.seq_no,
+            error_code: 48,
+            new_server_salt,
+        })
+    }
+
+    fn bad_msg_notification(msg: &Msg, error_code: i32) -> Vec<u8> {
+        boxed(&tl::mtproto::types::BadMsgNotification {
+            bad_msg_id: msg.msg_id,
+            bad_msg_seqno: msg.seq_no,
+            error_code,
+        })
+    }
+
+    fn gzip_packed(object: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(object).unwrap();
+        let packed_data = encoder.finish().unwrap();
+
+        let mut body = GzipPacked::CONSTRUCTOR_ID.to_le_bytes().to_vec();
+        body.resize(4 + tl::ser::bytes_len(packed_data.len()), 0);
+
+        let (bytes, _) = tl::ser::prepare_bytes(&mut body[4..], packed_data.len());
+        bytes.copy_from_slice(&packed_data);
+
+        body
+    }
+
+    fn boxed<X: tl::ser::SerializeUnchecked + tl::Identifiable>(x: &X) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.ser(&*tl::CalculatedLen::new(tl::ConstructorId::from_ref(x)));
+        body
+    }
+
+    fn msg_container(messages: &[(Msg, Vec<u8>)]) -> Vec<u8> {
+        let mut body = pack::MsgContainer::CONSTRUCTOR_ID.to_le_bytes().to_vec();
+        body.extend_from_slice(&(messages.len() as u32).to_le_bytes());
+
+        for (msg, message) in messages {
+            body.extend_from_slice(&msg.msg_id.to_le_bytes());
+            body.extend_from_slice(&msg.seq_no.to_le_bytes());
+            body.extend_from_slice(&(message.len() as i32).to_le_bytes());
+            body.extend_from_slice(message);
+        }
+
+        body
+    }
+
+    /// Poll the sender until the future completes.
+    async fn run_until<F: Future>(sender: &mut TestSender, future: F) -> F::Output {
+        let mut future = pin!(future);
+
+        poll_fn(|cx| {
+            loop {
+                if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                    return Poll::Ready(output);
+                }
+
+                match sender.poll(cx) {
+                    Poll::Ready(Ok(_)) => {}
+                    Poll::Ready(Err(err)) => panic!("{err}"),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+        })
+        .await
+    }
+
+    /// Poll the sender until the response is received.
+    async fn receive<X: tl::Function>(
+        sender: &mut TestSender,
+        response: Response<X>,
+    ) -> Result<X::Response, InvokeError> {
+        run_until(sender, response).await
+    }
+
+    fn ping(sender: &mut TestSender, ping_id: i64) -> Response<tl::mtproto::funcs::Ping> {
+        let func = tl::mtproto::funcs::Ping { ping_id };
+
+        sender.invoke(tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)))
+    }
+
+    #[tokio::test]
+    async fn test_sender_container_gzip_packed() {
+        let (mut sender, mut server) = new_sender();
+
+        let a = ping(&mut sender, 1);
+        let b = ping(&mut sender, 2);
+
+        let (a_id, b_id) = (a.msg_id(), b.msg_id());
+
+        let container = msg_container(&[
+            (
+                Msg {
+                    msg_id: a_id + 1,
+                    seq_no: 1,
+                },
+                rpc_result(a_id, &gzip_packed(&pong_body(a_id, 1))),
+            ),
+            (
+                Msg {
+                    msg_id: a_id + 5,
+                    seq_no: 3,
+                },
+                gzip_packed(&pong_body(b_id, 2)),
+            ),
+        ]);
+
+        let msg = Msg {
+            msg_id: a_id + 9,
+            seq_no: 4,
+        };
+
+        server
+            .write_all(&server_packet(msg, &container))
+            .await
+            .unwrap();
+
+        let tl::mtproto::enums::Pong::Pong(pong) = receive(&mut sender, a).await.unwrap();
+        assert_eq!((pong.msg_id, pong.ping_id), (a_id, 1));
+
+        let tl::mtproto::enums::Pong::Pong(pong) = receive(&mut sender, b).await.unwrap();
+        assert_eq!((pong.msg_id, pong.ping_id), (b_id, 2));
+    }
+
+    #[tokio::test]
+    async fn test_sender_gzip_packed_request() {
+        let (mut sender, mut server) = new_sender();
+
+        let func = tl::mtproto::funcs::ReqDhParams {
+            nonce: [1; 16],
+            server_nonce: [2; 16],
+            p: vec![3; 4],
+            q: vec![4; 4],
+            public_key_fingerprint: 5,
+            encrypted_data: vec![0; 1024],
+        };
+
+        let func = tl::ConstructorId::from_ref(&func);
+
+        let _req_dh_params = sender.invoke(tl::CalculatedLen::new(func));
+        let _ping = ping(&mut sender, 1);
+
+        flush(&mut sender).await;
+
+        let (_, _, body) = client_message(&mut server).await;
+        let messages = container_messages(&body);
+
+        let (_, packed) = &messages[0];
+        assert!(GzipPacked::is_packed(packed));
+
+        let mut serialized = Vec::new();
+        serialized.ser(func);
+
+        assert!(packed.len() < serialized.len());
+        let gzip_packed: GzipPacked = tl::de(&packed[4..]).unwrap();
+        assert_eq!(gzip_packed.unpack().unwrap(), serialized);
+
+        // Too small to be compressed.
+        let (_, ping) = &messages[1];
+        assert!(ping.starts_with(&tl::mtproto::funcs::Ping::CONSTRUCTOR_ID.to_le_bytes()));
+    }
+
+    #[tokio::test]
+    async fn test_sender_quick_ack() {
+        let (mut sender, mut server) = new_sender();
+
+        let func = tl::mtproto::funcs::Ping { ping_id: 1 };
+        let pong = sender
+            .invoke_with_quick_ack(tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)));
+        let pong_id = pong.msg_id();
+
+        flush(&mut sender).await;
+
+        let len = server.read_u32_le().await.unwrap();
+        assert_eq!(len, 0xeeeeeeee);
+
+        let len = server.read_u32_le().await.unwrap();
+        assert_ne!(len & 0x80000000, 0);
+
+        let mut packet = vec![0; (len & 0x7fffffff) as usize];
+        server.read_exact(&mut packet).await.unwrap();
+
+        assert_eq!(sender.quick_acks.len(), 1);
+
+        let msg = Msg {
+            msg_id: pong_id + 1,
+            seq_no: 1,
+        };
+
+        server
+            .write_all(&server_packet(
+                msg,
+                &rpc_result(pong_id, &pong_body(pong_id, 1)),
+            ))
+            .await
+            .unwrap();
+
+        receive(&mut sender, pong).await.unwrap();
+
+        // The quick ack is not expected anymore.
+        assert!(sender.quick_acks.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_msgs_ack() {
+        let (mut sender, mut server) = new_sender();
+
+        let pong = ping(&mut sender, 1);
+        let pong_id = pong.msg_id();
+
+        let msg_id = pong_id + 1;
+        let msg = Msg { msg_id, seq_no: 1 };
+
+        server
+            .write_all(&server_packet(
+                msg,
+                &rpc_result(pong_id, &pong_body(pong_id, 1)),
+            ))
+            .await
+            .unwrap();
+
+        receive(&mut sender, pong).await.unwrap();
+
+        let (_, _, body) = client_message(&mut server).await;
+        assert_eq!(container_messages(&body).len(), 1);
+
+        // Piggy-backed on the next request.
+        let _ping = ping(&mut sender, 2);
+
+        flush(&mut sender).await;
+
+        let (_, _, body) = client_message(&mut server).await;
+        let messages = container_messages(&body);
+
+        let tl::mtproto::enums::MsgsAck::MsgsAck(msgs_ack) = tl::de(&messages[1].1).unwrap();
+        assert_eq!(msgs_ack.msg_ids, [msg_id]);
+
+        // Sent on its own once the delay passes.
+        let msg_id = pong_id + 5;
+        let msg = Msg { msg_id, seq_no: 3 };
+
+        server
+            .write_all(&server_packet(msg, &pong_body(0, 0)))
+            .await
+            .unwrap();
+
+        let (_, container, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let messages = container_messages(&body);
+
+        assert_eq!(messages.len(), 1);
+
+        let tl::mtproto::enums::MsgsAck::MsgsAck(msgs_ack) = tl::de(&messages[0].1).unwrap();
+        assert_eq!(msgs_ack.msg_ids, [msg_id]);
+
+        // Sent again if the container is rejected.
+        let msg = Msg {
+            msg_id: pong_id + 9,
+            seq_no: 4,
+        };
+
+        server
+            .write_all(&server_packet(msg, &bad_server_salt(&container, 0x4321)))
+            .await
+            .unwrap();
+
+        let (salt, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let messages = container_messages(&body);
+
+        assert_eq!(salt, 0x4321);
+
+        let tl::mtproto::enums::MsgsAck::MsgsAck(msgs_ack) = tl::de(&messages[0].1).unwrap();
+        assert_eq!(msgs_ack.msg_ids, [msg_id]);
+    }
+
+    #[tokio::test]
+    async fn test_sender_bad_msg_notification() {
+        let (mut sender, mut server) = new_sender();
+
+        let pong = ping(&mut sender, 1);
+
+        flush(&mut sender).await;
+
+        let (_, container, body) = client_message(&mut server).await;
+        let (_, ping) = container_messages(&body).remove(0);
+
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 2,
+        };
+
+        server
+            .write_all(&server_packet(msg, &bad_server_salt(&container, 0x4321)))
+            .await
+            .unwrap();
+
+        let (salt, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (msg, resent) = container_messages(&body).remove(0);
+
+        assert_eq!(salt, 0x4321);
+        assert_eq!(resent, ping);
+        assert!(msg.msg_id > pong.msg_id());
+
+        // The server clock is 1000 seconds ahead.
+        let server_msg_id = msg.msg_id + (1000 << 32) + 1;
+
+        let server_msg = Msg {
+            msg_id: server_msg_id,
+            seq_no: 4,
+        };
+
+        server
+            .write_all(&server_packet(server_msg, &bad_msg_notification(&msg, 16)))
+            .await
+            .unwrap();
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (msg, resent) = container_messages(&body).remove(0);
+
+        assert_eq!(resent, ping);
+        assert!(msg.msg_id >> 32 >= (server_msg_id >> 32) - 1);
+
+        let pong_body = pong_body(msg.msg_id, 1);
+
+        let msg = Msg {
+            msg_id: server_msg_id + 4,
+            seq_no: 5,
+        };
+
+        server
+            .write_all(&server_packet(msg, &pong_body))
+            .await
+            .unwrap();
+
+        receive(&mut sender, pong).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sender_retries() {
+        let (mut sender, mut server) = new_sender();
+
+        let func = tl::mtproto::funcs::Ping { ping_id: 1 };
+
+        let pong = sender.invoke_with_config(
+            tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)),
+            InvokeConfig {
+                max_retries: 1,
+                ..InvokeConfig::default()
+            },
+        );
+
+        flush(&mut sender).await;
+
+        let (_, container, _) = client_message(&mut server).await;
+
+        // Routine resends do not count.
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 2,
+        };
+
+        server
+            .write_all(&server_packet(msg, &bad_server_salt(&container, 0x4321)))
+            .await
+            .unwrap();
+
+        let mut resent = Vec::new();
+
+        for seq_no in [4, 6] {
+            let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+            let (msg, _) = container_messages(&body).remove(0);
+
+            resent.push(msg.msg_id);
+
+            let server_msg = Msg {
+                msg_id: msg.msg_id + 1,
+                seq_no,
+            };
+
+            server
+                .write_all(&server_packet(server_msg, &bad_msg_notification(&msg, 17)))
+                .await
+                .unwrap();
+        }
+
+        assert!(resent[0] > container.msg_id);
+        assert!(resent[1] > resent[0]);
+
+        let err = receive(&mut sender, pong).await.unwrap_err();
+        assert!(matches!(err, InvokeError::Retries));
+    }
+
+    #[tokio::test]
+    async fn test_sender_new_session() {
+        let (mut sender, mut server) = new_sender();
+
+        let pong = ping(&mut sender, 1);
+
+        flush(&mut sender).await;
+
+        let (_, container, body) = client_message(&mut server).await;
+        let (_, ping) = container_messages(&body).remove(0);
+
+        // msg_seqno too high.
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 2,
+        };
+
+        server
+            .write_all(&server_packet(msg, &bad_msg_notification(&container, 33)))
+            .await
+            .unwrap();
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (msg, resent) = container_messages(&body).remove(0);
+
+        assert_eq!(resent, ping);
+        assert_eq!(msg.seq_no, 1);
+        assert_ne!(sender.session_id, SESSION_ID);
+
+        // The answer sent in the previous session is ignored.
+        let old = Msg {
+            msg_id: container.msg_id + 5,
+            seq_no: 3,
+        };
+
+        server
+            .write_all(&server_packet(
+                old,
+                &rpc_result(pong.msg_id(), &pong_body(pong.msg_id(), 1)),
+            ))
+            .await
+            .unwrap();
+
+        flush(&mut sender).await;
+
+        let new = Msg {
+            msg_id: container.msg_id + 9,
+            seq_no: 1,
+        };
+
+        server
+            .write_all(&server_packet_in(
+                sender.session_id,
+                new,
+                &rpc_result(msg.msg_id, &pong_body(msg.msg_id, 1)),
+            ))
+            .await
+            .unwrap();
+
+        receive(&mut sender, pong).await.unwrap();
+        // The container was accepted.
+        assert!(sender.containers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sender_time_offset() {
+        let (mut sender, mut server) = new_sender();
+
+        sender.set_time_offset(-5);
+
+        let pong = ping(&mut sender, 1);
+        let pong_id = pong.msg_id();
+
+        // The server clock is 20 seconds ahead of the local one.
+        let server_msg_id = pong_id + (25 << 32) + 1;
+
+        let msg = Msg {
+            msg_id: server_msg_id,
+            seq_no: 1,
+        };
+
+        server
+            .write_all(&server_packet(
+                msg,
+                &rpc_result(pong_id, &pong_body(pong_id, 1)),
+            ))
+            .await
+            .unwrap();
+
+        receive(&mut sender, pong).await.unwrap();
+
+        assert!((sender.time_offset() - 20).abs() <= 1);
+
+        let msg_id = ping(&mut sender, 2).msg_id();
+        assert!(((msg_id >> 32) - (server_msg_id >> 32)).abs() <= 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_future_salts() {
+        let (mut sender, mut server) = new_sender_with_config(SenderConfig {
+            future_salts: Some(2),
+            ping_interval: None,
+            state_req_delay: None,
+            ..SenderConfig::default()
+        });
+
+        flush(&mut sender).await;
+
+        let (salt, container, body) = client_message(&mut server).await;
+        let (msg, get_future_salts) = container_messages(&body).remove(0);
+
+        assert_eq!(salt, SALT);
+        assert_eq!(
+            get_future_salts,
+            [&0xb921bd04u32.to_le_bytes()[..], &2i32.to_le_bytes()].concat()
+        );
+
+        let now = MsgIds::new().server_time();
+
+        let mut future_salts = tl::mtproto::types::FutureSalts::CONSTRUCTOR_ID
+            .to_le_bytes()
+            .to_vec();
+        future_salts.extend_from_slice(&msg.msg_id.to_le_bytes());
+        future_salts.extend_from_slice(&now.to_le_bytes());
+        future_salts.extend_from_slice(&2u32.to_le_bytes());
+
+        for (valid_since, salt) in [(now + 1800, 0x2222i64), (now - 1800, 0x1111)] {
+            future_salts.extend_from_slice(&valid_since.to_le_bytes());
+            future_salts.extend_from_slice(&(valid_since + 3600).to_le_bytes());
+            future_salts.extend_from_slice(&salt.to_le_bytes());
+        }
+
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 1,
+        };
+
+        server
+            .write_all(&server_packet(msg, &future_salts))
+            .await
+            .unwrap();
+
+        // Wait for the acknowledgement of the salts.
+        let (salt, _, _) = run_until(&mut sender, client_message(&mut server)).await;
+        assert_eq!(salt, 0x1111);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_keepalive() {
+        let (mut sender, mut server) = new_sender_with_config(SenderConfig {
+            future_salts: None,
+            ping_interval: Some(Duration::from_secs(10)),
+            ping_timeout: Duration::from_secs(5),
+            state_req_delay: None,
+            ..SenderConfig::default()
+        });
+
+        let start = Instant::now();
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (msg, ping) = container_messages(&body).remove(0);
+
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+
+        assert!(
+            ping.starts_with(
+                &tl::mtproto::funcs::PingDelayDisconnect::CONSTRUCTOR_ID.to_le_bytes()
+            )
+        );
+        assert_eq!(ping[12..16], 15i32.to_le_bytes());
+
+        let pong = Msg {
+            msg_id: msg.msg_id + 1,
+            seq_no: 0,
+        };
+
+        server
+            .write_all(&server_packet(pong, &pong_body(msg.msg_id, 0)))
+            .await
+            .unwrap();
+
+        let event = poll_fn(|cx| sender.poll(cx)).await.unwrap();
+        assert!(matches!(event, SenderEvent::Pong { .. }));
+        assert!(sender.rtt().is_some());
+
+        // The next ping is left unanswered.
+        let _ = run_until(&mut sender, client_message(&mut server)).await;
+
+        let err = poll_fn(|cx| sender.poll(cx)).await.unwrap_err();
+        assert!(matches!(err, SenderError::PingTimeout));
+
+        assert_eq!(start.elapsed(), Duration::from_secs(25));
+    }
+
+    #[tokio::test]
+    async fn test_sender_new_session_created() {
+        let (mut sender, mut server) = new_sender();
+
+        let pong = ping(&mut sender, 1);
+
+        flush(&mut sender).await;
+
+        let (_, container, body) = client_message(&mut server).await;
+        let (_, ping) = container_messages(&body).remove(0);
+
+        let mut new_session_created = tl::mtproto::types::NewSessionCreated::CONSTRUCTOR_ID
+            .to_le_bytes()
+            .to_vec();
+        new_session_created.extend_from_slice(&(container.msg_id + 1).to_le_bytes());
+        new_session_created.extend_from_slice(&0x1111i64.to_le_bytes());
+        new_session_created.extend_from_slice(&0x4321i64.to_le_bytes());
+
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 1,
+        };
+
+        server
+            .write_all(&server_packet(msg, &new_session_created))
+            .await
+            .unwrap();
+
+        let event = poll_fn(|cx| sender.poll(cx)).await.unwrap();
+        assert!(matches!(event, SenderEvent::UpdatesGap));
+
+        let (salt, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+
+        // The acknowledgement of `new_session_created` is sent along.
+        let (resent_msg, _) = container_messages(&body)
+            .into_iter()
+            .find(|(_, resent)| *resent == ping)
+            .unwrap();
+
+        assert_eq!(salt, 0x4321);
+
+        let pong_body = pong_body(resent_msg.msg_id, 1);
+
+        let msg = Msg {
+            msg_id: container.msg_id + 5,
+            seq_no: 3,
+        };
+
+        server
+            .write_all(&server_packet(msg, &pong_body))
+            .await
+            .unwrap();
+
+        receive(&mut sender, pong).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sender_msg_id_validation() {
+        let (mut sender, mut server) = new_sender();
+
+        let mut pong = ping(&mut sender, 1);
+
+        flush(&mut sender).await;
+
+        let (_, container, _) = client_message(&mut server).await;
+
+        let pong_body = pong_body(pong.msg_id(), 1);
+
+        // Created too long ago.
+        let msg = Msg {
+            msg_id: container.msg_id - (600 << 32) + 3,
+            seq_no: 0,
+        };
+
+        server
+            .write_all(&server_packet(msg, &pong_body))
+            .await
+            .unwrap();
+
+        flush(&mut sender).await;
+
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        assert!(Pin::new(&mut pong).poll(&mut cx).is_pending());
+
+        // A response does not move the clock before being checked.
+        let msg = Msg {
+            msg_id: container.msg_id + (600 << 32) + 1,
+            seq_no: 0,
+        };
+
+        server
+            .write_all(&server_packet(msg, &pong_body))
+            .await
+            .unwrap();
+
+        flush(&mut sender).await;
+
+        assert!(Pin::new(&mut pong).poll(&mut cx).is_pending());
+        assert_eq!(sender.time_offset(), 0);
+
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 0,
+        };
+
+        server
+            .write_all(&server_packet(msg, &pong_body))
+            .await
+            .unwrap();
+
+        receive(&mut sender, pong).await.unwrap();
+
+        let msg = Msg {
+            msg_id: container.msg_id + 4,
+            seq_no: 0,
+        };
+
+        server
+            .write_all(&server_packet(msg, &pong_body))
+            .await
+            .unwrap();
+
+        let err = poll_fn(|cx| sender.poll(cx)).await.unwrap_err();
+        assert!(matches!(err, SenderError::InvalidMsgId(_)));
+    }
+
+    #[tokio::test]
+    async fn test_sender_replayed_container() {
+        let (mut sender, mut server) = new_sender();
+
+        let _pong = ping(&mut sender, 1);
+
+        flush(&mut sender).await;
+
+        let (_, container, _) = client_message(&mut server).await;
+
+        let state_req = |msg_id| {
+            let msgs_state_req = boxed(&tl::mtproto::types::MsgsStateReq {
+                msg_ids: vec![container.msg_id],
+            });
+
+            let msg = Msg { msg_id, seq_no: 1 };
+
+            (msg, msgs_state_req)
+        };
+
+        let inner_msg_id = container.msg_id + 3;
+
+        // The same message is replayed in another container.
+        for msg_id in [container.msg_id + 7, container.msg_id + 11] {
+            let msg = Msg { msg_id, seq_no: 2 };
+
+            server
+                .write_all(&server_packet(
+                    msg,
+                    &msg_container(&[state_req(inner_msg_id)]),
+                ))
+                .await
+                .unwrap();
+        }
+
+        let last_msg_id = container.msg_id + 15;
+
+        let (msg, msgs_state_req) = state_req(last_msg_id);
+
+        server
+            .write_all(&server_packet(msg, &msgs_state_req))
+            .await
+            .unwrap();
+
+        let mut req_msg_ids = Vec::new();
+
+        while req_msg_ids.last() != Some(&last_msg_id) {
+            let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+
+            for (_, body) in container_messages(&body) {
+                if let Ok(tl::mtproto::enums::MsgsStateInfo::MsgsStateInfo(info)) = tl::de(&body) {
+                    req_msg_ids.push(info.req_msg_id);
+                }
+            }
+        }
+
+        assert_eq!(req_msg_ids, [inner_msg_id, last_msg_id]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_container_answer() {
+        let (mut sender, mut server) = new_sender();
+
+        let pong = ping(&mut sender, 1);
+        let ping_msg_id = pong.msg_id();
+
+        flush(&mut sender).await;
+
+        let (_, container, _) = client_message(&mut server).await;
+
+        let answer_msg_id = container.msg_id + 1;
+
+        let answer = Msg {
+            msg_id: answer_msg_id,
+            seq_no: 1,
+        };
+
+        let msg = Msg {
+            msg_id: container.msg_id + 5,
+            seq_no: 2,
+        };
+
+        server
+            .write_all(&server_packet(
+                msg,
+                &msg_container(&[(answer, pong_body(ping_msg_id, 1))]),
+            ))
+            .await
+            .unwrap();
+
+        receive(&mut sender, pong).await.unwrap();
+
+        let msg_detailed_info = boxed(&tl::mtproto::types::MsgDetailedInfo {
+            msg_id: ping_msg_id,
+            answer_msg_id,
+            bytes: 32,
+            status: 0,
+        });
+
+        let msg = Msg {
+            msg_id: container.msg_id + 9,
+            seq_no: 2,
+        };
+
+        server
+            .write_all(&server_packet(msg, &msg_detailed_info))
+            .await
+            .unwrap();
+
+        // The answer is acknowledged again instead of being requested.
+        loop {
+            let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+
+            let messages = container_messages(&body);
+
+            assert!(!messages.iter().any(|(_, body)| {
+                body.starts_with(&tl::mtproto::types::MsgResendReq::CONSTRUCTOR_ID.to_le_bytes())
+            }));
+
+            if messages.iter().any(|(_, body)| {
+                matches!(
+                    tl::de(body),
+                    Ok(tl::mtproto::enums::MsgsAck::MsgsAck(ack)) if ack.msg_ids.contains(&answer_msg_id)
+                )
+            }) {
+                break;
+            }
+        }
+
+        // Messages are created before their container.
+        let msg = Msg {
+            msg_id: container.msg_id + 13,
+            seq_no: 2,
+        };
+
+        let inner = Msg {
+            msg_id: container.msg_id + 17,
+            seq_no: 2,
+        };
+
+        server
+            .write_all(&server_packet(
+                msg,
+                &msg_container(&[(inner, msg_detailed_info)]),
+            ))
+            .await
+            .unwrap();
+
+        let err = poll_fn(|cx| sender.poll(cx)).await.unwrap_err();
+        assert!(matches!(err, SenderError::InvalidMsgId(_)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_msgs_state() {
+        let (mut sender, mut server) = new_sender_with_config(SenderConfig {
+            future_salts: None,
+            ping_interval: None,
+            state_req_delay: Some(Duration::from_secs(10)),
+            ..SenderConfig::default()
+        });
+
+        /// Read the client messages until one has the constructor.
+        async fn service_message(
+            sender: &mut TestSender,
+            server: &mut DuplexStream,
+            constructor_id: u32,
+        ) -> (Msg, Vec<u8>) {
+            loop {
+                let (_, _, body) = run_until(sender, client_message(server)).await;
+
+                if let Some(message) = container_messages(&body)
+                    .into_iter()
+                    .find(|(_, body)| body.starts_with(&constructor_id.to_le_bytes()))
+                {
+                    return message;
+                }
+            }
+        }
+
+        let pong = ping(&mut sender, 1);
+
+        flush(&mut sender).await;
+
+        let (_, container, _) = client_message(&mut server).await;
+
+        // The ping is not answered in time.
+        let (msg, body) = service_message(
+            &mut sender,
+            &mut server,
+            tl::mtproto::types::MsgsStateReq::CONSTRUCTOR_ID,
+        )
+        .await;
+
+        let tl::mtproto::enums::MsgsStateReq::MsgsStateReq(req) = tl::de(&body).unwrap();
+        assert_eq!(req.msg_ids, [pong.msg_id()]);
+
+        let msgs_state_info = boxed(&tl::mtproto::types::MsgsStateInfo {
+            req_msg_id: msg.msg_id,
+            info: vec![2],
+        });
+
+        let info_msg_id = msg.msg_id + 1;
+
+        let msg = Msg {
+            msg_id: info_msg_id,
+            seq_no: 1,
+        };
+
+        server
+            .write_all(&server_packet(msg, &msgs_state_info))
+            .await
+            .unwrap();
+
+        // Not received by the server.
+        let (resent_msg, _) = service_message(
+            &mut sender,
+            &mut server,
+            tl::mtproto::funcs::Ping::CONSTRUCTOR_ID,
+        )
+        .await;
+
+        assert!(resent_msg.msg_id > container.msg_id);
+
+        let msgs_state_req = boxed(&tl::mtproto::types::MsgsStateReq {
+            msg_ids: vec![info_msg_id, info_msg_id + 4, info_msg_id + 64],
+        });
+
+        let msg = Msg {
+            msg_id: info_msg_id + 8,
+            seq_no: 3,
+        };
+
+        let req_msg_id = msg.msg_id;
+
+        server
+            .write_all(&server_packet(msg, &msgs_state_req))
+            .await
+            .unwrap();
+
+        let (_, body) = service_message(
+            &mut sender,
+            &mut server,
+            tl::mtproto::types::MsgsStateInfo::CONSTRUCTOR_ID,
+        )
+        .await;
+
+        let tl::mtproto::enums::MsgsStateInfo::MsgsStateInfo(info) = tl::de(&body).unwrap();
+
+        assert_eq!(info.req_msg_id, req_msg_id);
+        assert_eq!(info.info.len(), 3);
+        assert_eq!(info.info[0] & 7, 4);
+        assert_eq!(info.info[1..], [2, 3]);
+
+        // The answer to the ping was lost.
+        let answer_msg_id = info_msg_id + 12;
+
+        let msg_detailed_info = boxed(&tl::mtproto::types::MsgDetailedInfo {
+            msg_id: resent_msg.msg_id,
+            answer_msg_id,
+            bytes: 32,
+            status: 0,
+        });
+
+        let msg = Msg {
+            msg_id: info_msg_id + 16,
+            seq_no: 4,
+        };
+
+        server
+            .write_all(&server_packet(msg, &msg_detailed_info))
+            .await
+            .unwrap();
+
+        let (_, body) = service_message(
+            &mut sender,
+            &mut server,
+            tl::mtproto::types::MsgResendReq::CONSTRUCTOR_ID,
+        )
+        .await;
+
+        let tl::mtproto::enums::MsgResendReq::MsgResendReq(req) = tl::de(&body).unwrap();
+        assert_eq!(req.msg_ids, [answer_msg_id]);
+
+        let pong_body = pong_body(resent_msg.msg_id, 1);
+
+        let msg = Msg {
+            msg_id: answer_msg_id,
+            seq_no: 5,
+        };
+
+        server
+            .write_all(&server_packet(msg, &pong_body))
+            .await
+            .unwrap();
+
+        receive(&mut sender, pong).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sender_cancel() {
+        let (mut sender, mut server) = new_sender();
+
+        let a = ping(&mut sender, 1);
+        let b = ping(&mut sender, 2);
+
+        let b_id = b.msg_id();
+
+        // Not sent yet.
+        drop(a);
+
+        flush(&mut sender).await;
+
+        let (_, container, body) = client_message(&mut server).await;
+        let messages = container_messages(&body);
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0.msg_id, b_id);
+
+        // Already sent.
+        drop(b);
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (msg, drop_answer) = container_messages(&body).remove(0);
+
+        assert!(
+            drop_answer
+                .starts_with(&tl::mtproto::funcs::RpcDropAnswer::CONSTRUCTOR_ID.to_le_bytes())
+        );
+        assert_eq!(drop_answer[4..12], b_id.to_le_bytes());
+
+        let answer = rpc_result(
+            msg.msg_id,
+            &boxed(&tl::mtproto::types::RpcAnswerDroppedRunning {}),
+        );
+
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 1,
+        };
+
+        server
+            .write_all(&server_packet(msg, &answer))
+            .await
+            .unwrap();
+
+        flush(&mut sender).await;
+        flush(&mut sender).await;
+
+        assert!(sender.pending.is_empty());
+        assert!(sender.drop_answers.is_empty());
+
+        let c = ping(&mut sender, 3);
+
+        flush(&mut sender).await;
+
+        let (_, rejected, _) = client_message(&mut server).await;
+
+        let msg = Msg {
+            msg_id: container.msg_id + 5,
+            seq_no: 2,
+        };
+
+        server
+            .write_all(&server_packet(msg, &bad_server_salt(&rejected, SALT)))
+            .await
+            .unwrap();
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (resent, _) = container_messages(&body).remove(0);
+
+        assert_ne!(resent.msg_id, c.msg_id());
+
+        // Sent again with another identifier.
+        drop(c);
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (_, drop_answer) = container_messages(&body).remove(0);
+
+        assert_eq!(drop_answer[4..12], resent.msg_id.to_le_bytes());
+        assert!(sender.resent.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_timeout() {
+        let (mut sender, mut server) = new_sender();
+
+        let func = tl::mtproto::funcs::Ping { ping_id: 1 };
+
+        let pong = sender.invoke_with_config(
+            tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)),
+            InvokeConfig {
+                timeout: Some(Duration::from_secs(5)),
+                ..InvokeConfig::default()
+            },
+        );
+
+        let pong_id = pong.msg_id();
+
+        flush(&mut sender).await;
+
+        let _ = client_message(&mut server).await;
+
+        let start = Instant::now();
+
+        let err = receive(&mut sender, pong).await.unwrap_err();
+        assert!(matches!(err, InvokeError::Sender(SenderError::Timeout)));
+
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (_, drop_answer) = container_messages(&body).remove(0);
+
+        assert!(
+            drop_answer
+                .starts_with(&tl::mtproto::funcs::RpcDropAnswer::CONSTRUCTOR_ID.to_le_bytes())
+        );
+        assert_eq!(drop_answer[4..12], pong_id.to_le_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_sender_reconnect() {
+        let (mut sender, mut server) = new_sender();
+
+        let a = ping(&mut sender, 1);
+
+        let func = tl::mtproto::funcs::Ping { ping_id: 2 };
+
+        let b = sender.invoke_with_config(
+            tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)),
+            InvokeConfig {
+                max_retries: 0,
+                ..InvokeConfig::default()
+            },
+        );
+
+        flush(&mut sender).await;
+
+        let (_, _, body) = client_message(&mut server).await;
+        let (_, ping) = container_messages(&body).remove(0);
+
+        // The connection is lost before the responses are received.
+        drop(server);
+
+        let (reader, writer, mut server) = connect();
+        sender.reconnect(reader, writer);
+
+        let err = receive(&mut sender, b).await.unwrap_err();
+        assert!(matches!(err, InvokeError::Retries));
+
+        let (_, container, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let messages = container_messages(&body);
+
+        assert_eq!(messages.len(), 1);
+
+        let (msg, resent) = &messages[0];
+
+        assert_eq!(*resent, ping);
+
+        let pong_body = pong_body(msg.msg_id, 1);
+
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 0,
+        };
+
+        server
+            .write_all(&server_packet(msg, &pong_body))
+            .await
+            .unwrap();
+
+        receive(&mut sender, a).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sender_reconnect_received() {
+        let (mut sender, mut server) = new_sender();
+
+        let a = ping(&mut sender, 1);
+
+        flush(&mut sender).await;
+
+        let (_, container, _) = client_message(&mut server).await;
+
+        let _b = ping(&mut sender, 2);
+        let c = ping(&mut sender, 3);
+
+        let (a_id, c_id) = (a.msg_id(), c.msg_id());
+
+        flush(&mut sender).await;
+
+        let (_, _, body) = client_message(&mut server).await;
+        let messages = container_messages(&body);
+
+        let msgs_ack = boxed(&tl::mtproto::types::MsgsAck {
+            msg_ids: vec![a_id],
+        });
+
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 0,
+        };
+
+        server
+            .write_all(&server_packet(msg, &msgs_ack))
+            .await
+            .unwrap();
+
+        flush(&mut sender).await;
+
+        // The connection is lost before the responses are received.
+        drop(c);
+        drop(server);
+
+        let (reader, writer, mut server) = connect();
+        sender.reconnect(reader, writer);
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let resent = container_messages(&body);
+
+        assert_eq!(resent.len(), 3);
+        assert_eq!(resent[0].1, messages[0].1);
+
+        let tl::mtproto::enums::MsgsStateReq::MsgsStateReq(req) = tl::de(&resent[1].1).unwrap();
+        assert_eq!(req.msg_ids, [a_id]);
+
+        assert!(
+            resent[2]
+                .1
+                .starts_with(&tl::mtproto::funcs::RpcDropAnswer::CONSTRUCTOR_ID.to_le_bytes())
+        );
+        assert_eq!(resent[2].1[4..12], c_id.to_le_bytes());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_reconnect_ping_timeout() {
+        let (mut sender, mut server) = new_sender_with_config(SenderConfig {
+            future_salts: None,
+            ping_interval: Some(Duration::from_secs(10)),
+            ping_timeout: Duration::from_secs(5),
+            state_req_delay: None,
+            ..SenderConfig::default()
+        });
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (_, ping) = container_messages(&body).remove(0);
+
+        let err = poll_fn(|cx| sender.poll(cx)).await.unwrap_err();
+        assert!(matches!(err, SenderError::PingTimeout));
+
+        let (reader, writer, mut server) = connect();
+        sender.reconnect(reader, writer);
+
+        let start = Instant::now();
+
+        // The unanswered ping is not sent again, a new one follows the interval.
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let (msg, resent) = container_messages(&body).remove(0);
+
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        assert_ne!(resent, ping);
+
+        let pong = Msg {
+            msg_id: msg.msg_id + 1,
+            seq_no: 0,
+        };
+
+        server
+            .write_all(&server_packet(pong, &pong_body(msg.msg_id, 0)))
+            .await
+            .unwrap();
+
+        let event = poll_fn(|cx| sender.poll(cx)).await.unwrap();
+        assert!(matches!(event, SenderEvent::Pong { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_sender_ordered() {
+        let (mut sender, mut server) = new_sender();
+
+        let mut batch = sender.ordered();
+
+        let pongs: Vec<_> = (1..=3)
+            .map(|ping_id| {
+                let func = tl::mtproto::funcs::Ping { ping_id };
+
+                batch.invoke(tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)))
+            })
+            .collect();
+
+        flush(&mut sender).await;
+
+        /// Returns the identifier each function is invoked after.
+        fn invoked_after(messages: &[(Msg, Vec<u8>)]) -> Vec<Option<MsgId>> {
+            messages
+                .iter()
+                .map(|(_, body)| {
+                    body.starts_with(&INVOKE_AFTER_MSG.to_le_bytes())
+                        .then(|| i64::from_le_bytes(body[4..12].try_into().unwrap()))
+                })
+                .collect()
+        }
+
+        let (_, container, body) = client_message(&mut server).await;
+        let messages = container_messages(&body);
+
+        assert_eq!(
+            invoked_after(&messages),
+            [None, Some(pongs[0].msg_id()), Some(pongs[1].msg_id())]
+        );
+        assert_eq!(messages[2].1[12..16], messages[0].1[..4]);
+
+        fn drop_answer(msg: &Msg) -> Vec<u8> {
+            boxed(&tl::mtproto::funcs::RpcDropAnswer {
+                req_msg_id: msg.msg_id,
+            })
+        }
+
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 2,
+        };
+
+        server
+            .write_all(&server_packet(msg, &bad_server_salt(&container, 0x4321)))
+            .await
+            .unwrap();
+
+        // The functions rejected together are resent in order.
+        let (_, container, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let resent = container_messages(&body);
+
+        assert_eq!(resent.len(), 3);
+        assert!(resent[0].0.msg_id > messages[2].0.msg_id);
+        assert_eq!(
+            invoked_after(&resent),
+            [None, Some(resent[0].0.msg_id), Some(resent[1].0.msg_id)]
+        );
+
+        let msg = Msg {
+            msg_id: container.msg_id + 1,
+            seq_no: 4,
+        };
+
+        server
+            .write_all(&server_packet(msg, &bad_server_salt(&resent[0].0, 0x4321)))
+            .await
+            .unwrap();
+
+        // The following functions already sent are dropped before being resent.
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+        let messages = container_messages(&body);
+
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[1].1, drop_answer(&resent[1].0));
+        assert_eq!(messages[3].1, drop_answer(&resent[2].0));
+        assert_eq!(
+            [0, 2, 4].map(|i| invoked_after(&messages[i..=i])[0]),
+            [None, Some(messages[0].0.msg_id), Some(messages[2].0.msg_id)]
+        );
+        assert_eq!(messages[4].1[12..], resent[2].1[12..]);
+
+        // The last function is executed after the first one once the second
+        // one is cancelled.
+        let mut pongs = pongs.into_iter();
+        let _first = pongs.next();
+        drop(pongs.next());
+
+        flush(&mut sender).await;
+
+        let (_, _, body) = client_message(&mut server).await;
+        let cancelled = container_messages(&body);
+
+        assert_eq!(cancelled.len(), 3);
+        assert_eq!(cancelled[0].1, drop_answer(&messages[2].0));
+        assert_eq!(cancelled[1].1, drop_answer(&messages[4].0));
+        assert_eq!(invoked_after(&cancelled[2..]), [Some(messages[0].0.msg_id)]);
+        assert_eq!(cancelled[2].1[12..], resent[2].1[12..]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_container_limits() {
+        let (mut sender, mut server) = new_sender_with_config(SenderConfig {
+            future_salts: None,
+            ping_interval: None,
+            state_req_delay: None,
+            gzip_threshold: None,
+            container_capacity: 4096,
+            max_container_len: 2,
+            flush_delay: Duration::from_millis(100),
+            ..SenderConfig::default()
+        });
+
+        let a = ping(&mut sender, 1);
+        let b = ping(&mut sender, 2);
+        let c = ping(&mut sender, 3);
+
+        // The first container is full and queued, the second one waits for more messages.
+        flush(&mut sender).await;
+        assert!(!sender.container.is_empty());
+
+        let (_, _, body) = client_message(&mut server).await;
+        let messages = container_messages(&body);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].0.msg_id, a.msg_id());
+        assert_eq!(messages[1].0.msg_id, b.msg_id());
+
+        let func = tl::mtproto::funcs::ReqDhParams {
+            nonce: [1; 16],
+            server_nonce: [2; 16],
+            p: vec![3; 4],
+            q: vec![4; 4],
+            public_key_fingerprint: 5,
+            encrypted_data: vec![0; 8192],
+        };
+
+        let req_dh_params =
+            sender.invoke(tl::CalculatedLen::new(tl::ConstructorId::from_ref(&func)));
+        flush(&mut sender).await;
+
+        // Messages pushed before are sent first.
+        let (_, _, body) = client_message(&mut server).await;
+        let messages = container_messages(&body);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0.msg_id, c.msg_id());
+
+        // Too large for a container.
+        let (_, msg, body) = client_message(&mut server).await;
+        assert_eq!(msg.msg_id, req_dh_params.msg_id());
+        assert!(body.starts_with(&tl::mtproto::funcs::ReqDhParams::CONSTRUCTOR_ID.to_le_bytes()));
+        assert_eq!(body.len(), 4 + 16 + 16 + 8 + 8 + 8 + 8196);
+
+        let d = ping(&mut sender, 4);
+        flush(&mut sender).await;
+        assert!(!sender.container.is_empty());
+
+        time::advance(Duration::from_millis(100)).await;
+        flush(&mut sender).await;
+
+        let (_, _, body) = client_message(&mut server).await;
+        let messages = container_messages(&body);
+        assert_eq!(messages[0].0.msg_id, d.msg_id());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_large_service_messages() {
+        let (mut sender, mut server) = new_sender_with_config(SenderConfig {
+            future_salts: None,
+            ping_interval: None,
+            state_req_delay: None,
+            container_capacity: 4096,
+            ..SenderConfig::default()
+        });
+
+        let _pong = ping(&mut sender, 1);
+
+        flush(&mut sender).await;
+
+        let (_, container, _) = client_message(&mut server).await;
+
+        // More acknowledgements than fit into a container.
+        let messages: Vec<_> = (0..600)
+            .map(|i| {
+                let msg = Msg {
+                    msg_id: container.msg_id + 3 + i * 4,
+                    seq_no: 1 + i as i32 * 2,
+                };
+
+                (msg, vec![0; 4])
+            })
+            .collect();
+
+        let msg = Msg {
+            msg_id: container.msg_id + 3 + 600 * 4,
+            seq_no: 1200,
+        };
+
+        server
+            .write_all(&server_packet(msg, &msg_container(&messages)))
+            .await
+            .unwrap();
+
+        let mut acks = Vec::new();
+
+        while acks.len() < messages.len() {
+            let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+
+            let (_, msgs_ack) = container_messages(&body).remove(0);
+            let tl::mtproto::enums::MsgsAck::MsgsAck(x) = tl::de(&msgs_ack).unwrap();
+
+            assert!(x.msg_ids.len() < messages.len());
+
+            acks.extend(x.msg_ids);
+        }
+
+        assert!(acks.iter().eq(messages.iter().map(|(msg, _)| &msg.msg_id)));
+
+        // The state of more messages than fit into a container.
+        let msgs_state_req = boxed(&tl::mtproto::types::MsgsStateReq {
+            msg_ids: vec![container.msg_id; 5000],
+        });
+
+        let msg = Msg {
+            msg_id: container.msg_id + 7 + 600 * 4,
+            seq_no: 1201,
+        };
+
+        let req_msg_id = msg.msg_id;
+
+        server
+            .write_all(&server_packet(msg, &msgs_state_req))
+            .await
+            .unwrap();
+
+        let (_, _, body) = run_until(&mut sender, client_message(&mut server)).await;
+
+        let tl::mtproto::enums::MsgsStateInfo::MsgsStateInfo(info) = tl::de(&body).unwrap();
+
+        assert_eq!(info.req_msg_id, req_msg_id);
+        assert_eq!(info.info.len(), 5000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sender_flush_delay() {
+        let (mut sender, mut server) = new_sender_with_config(SenderConfig {
+            future_salts: None,
+            ping_interval: None,
+            state_req_delay: None,
+            flush_delay: Duration::from_millis(100),
+            ..SenderConfig::default()
+        });
+
+        let a = ping(&mut sender, 1);
+
+        let start = Instant::now();
+        let mut polls = 0;
+
+        let mut message = pin!(client_message(&mut server));
+
+        // The sender sleeps until the delay elapses instead of waking itself up.
+        let (_, _, body) = poll_fn(|cx| {
+            polls += 1;
+            assert!(polls < 10, "busy loop");
+
+            assert!(sender.poll(cx).is_pending());
+
+            message.as_mut().poll(cx)
+        })
+        .await;
+
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+
+        let messages = container_messages(&body);
+        assert_eq!(messages[0].0.msg_id, a.msg_id());
     }
 }
@@ -0,0 +1,45 @@
+use crate::mtproto::MsgId;
+use crate::reader::ReaderDriver;
+use crate::sender::{InvokeConfig, Response, Sender};
+use crate::tl;
+use crate::transport::Transport;
+use crate::writer::WriterDriver;
+
+/// Functions to be executed by the server in the order they are invoked,
+/// each one wrapped in `invokeAfterMsg` referencing the previous one.
+///
+/// A function resent with a new identifier resends the following ones, after
+/// dropping the copies already sent. The functions following a cancelled one
+/// are executed after the function it was to be executed after.
+///
+/// ---
+/// https://core.telegram.org/api/invoking#invokeaftermsg-and-invokeaftermsgs
+pub struct OrderedBatch<'a, T: Transport, R: ReaderDriver, W: WriterDriver> {
+    sender: &'a mut Sender<T, R, W>,
+    last: Option<MsgId>,
+}
+
+impl<'a, T: Transport, R: ReaderDriver, W: WriterDriver> OrderedBatch<'a, T, R, W> {
+    pub(super) fn new(sender: &'a mut Sender<T, R, W>) -> Self {
+        Self { sender, last: None }
+    }
+
+    pub fn invoke<X: tl::Function>(
+        &mut self,
+        func: tl::CalculatedLen<'_, tl::ConstructorId<X>>,
+    ) -> Response<X> {
+        self.invoke_with_config(func, InvokeConfig::default())
+    }
+
+    pub fn invoke_with_config<X: tl::Function>(
+        &mut self,
+        func: tl::CalculatedLen<'_, tl::ConstructorId<X>>,
+        config: InvokeConfig,
+    ) -> Response<X> {
+        let response = self.sender.invoke_after(func, config, self.last);
+
+        self.last = Some(response.msg_id());
+
+        response
+    }
+}
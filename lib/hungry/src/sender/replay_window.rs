@@ -0,0 +1,86 @@
+use std::collections::BTreeSet;
+
+use crate::mtproto::MsgId;
+
+/// Identifiers of the last messages received from the server,
+/// to ignore the messages replayed by an attacker.
+///
+/// ---
+/// https://core.telegram.org/mtproto/security_guidelines#checking-msg-id
+pub(super) struct ReplayWindow {
+    msg_ids: BTreeSet<MsgId>,
+    capacity: usize,
+}
+
+impl ReplayWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            msg_ids: BTreeSet::new(),
+            capacity,
+        }
+    }
+
+    /// Returns `true` if the message with the identifier is to be ignored,
+    /// because it was already received or is older than all stored ones.
+    pub fn contains(&self, msg_id: MsgId) -> bool {
+        matches!(self.state(msg_id), 1 | 4)
+    }
+
+    /// Returns the state of the message as reported by `msgs_state_info`:
+    /// 1 if nothing is known about it, 2 if it was not received,
+    /// 3 if it was not received yet, or 4 if it was received.
+    pub fn state(&self, msg_id: MsgId) -> u8 {
+        if self.msg_ids.contains(&msg_id) {
+            4
+        } else if self.msg_ids.len() >= self.capacity
+            && self.msg_ids.first().is_some_and(|&first| msg_id < first)
+        {
+            1
+        } else if self.msg_ids.last().is_none_or(|&last| msg_id > last) {
+            3
+        } else {
+            2
+        }
+    }
+
+    /// Store the identifier, forgetting the oldest one once full.
+    pub fn insert(&mut self, msg_id: MsgId) {
+        self.msg_ids.insert(msg_id);
+
+        if self.msg_ids.len() > self.capacity {
+            self.msg_ids.pop_first();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sender_replay_window() {
+        let mut window = ReplayWindow::new(2);
+
+        assert!(!window.contains(13));
+        window.insert(13);
+
+        assert!(window.contains(13));
+        // Older ids are accepted until the window is full.
+        assert!(!window.contains(9));
+        window.insert(9);
+
+        assert!(window.contains(5));
+        assert!(!window.contains(17));
+        window.insert(17);
+
+        assert!(window.contains(9));
+        assert!(window.contains(13));
+        assert!(window.contains(17));
+        assert!(!window.contains(21));
+
+        assert_eq!(window.state(9), 1);
+        assert_eq!(window.state(13), 4);
+        assert_eq!(window.state(15), 2);
+        assert_eq!(window.state(21), 3);
+    }
+}
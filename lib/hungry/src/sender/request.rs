@@ -0,0 +1,43 @@
+use tokio::time::Instant;
+
+use crate::mtproto::MsgId;
+use crate::sender::response::ResponseSender;
+
+/// Function sent to the server, kept until the response is received
+/// to be resent if the server rejects the message.
+pub(super) struct Request {
+    /// Identifier of the message the function was first sent in,
+    /// by which it is cancelled.
+    pub msg_id: MsgId,
+    /// Serialized function, possibly `gzip_packed`.
+    pub body: Vec<u8>,
+    pub quick_ack: bool,
+    /// Container the function was last sent in.
+    pub container: Option<MsgId>,
+    /// Whether the server acknowledged the message the function was last sent in.
+    pub received: bool,
+    /// When the function was last sent, or its state last requested.
+    pub sent: Instant,
+    /// When the function fails if not answered.
+    pub deadline: Option<Instant>,
+    /// Number of times the function may still be sent again.
+    pub retries: u32,
+    /// Function to be executed before, referenced by `invokeAfterMsg`.
+    pub after: Option<MsgId>,
+    pub sender: ResponseSender,
+}
+
+impl Request {
+    /// Reference the new identifier of the function to be executed before,
+    /// or unwrap the function from `invokeAfterMsg` if there is none.
+    pub fn set_after(&mut self, msg_id: Option<MsgId>) {
+        match msg_id {
+            Some(msg_id) => self.body[4..12].copy_from_slice(&msg_id.to_le_bytes()),
+            None => {
+                self.body.drain(..12);
+            }
+        }
+
+        self.after = msg_id;
+    }
+}
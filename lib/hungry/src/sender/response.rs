@@ -0,0 +1,86 @@
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use bytes::BytesMut;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::mtproto::MsgId;
+use crate::sender::InvokeError;
+use crate::tl;
+
+pub(super) type ResponseSender = oneshot::Sender<Result<BytesMut, InvokeError>>;
+
+/// Response to an invoked function, resolved by the matching `rpc_result`.
+///
+/// The [`Sender`] must be polled for the response to be received.
+/// Dropping the response before it is received cancels the request.
+///
+/// [`Sender`]: crate::Sender
+#[must_use = "the response is discarded unless awaited"]
+pub struct Response<X: tl::Function> {
+    msg_id: MsgId,
+    receiver: oneshot::Receiver<Result<BytesMut, InvokeError>>,
+    /// Notifies the [`Sender`] about the cancellation.
+    ///
+    /// [`Sender`]: crate::Sender
+    cancel: mpsc::UnboundedSender<MsgId>,
+    received: bool,
+    _marker: PhantomData<fn() -> X>,
+}
+
+impl<X: tl::Function> Response<X> {
+    pub(super) fn new(
+        msg_id: MsgId,
+        cancel: mpsc::UnboundedSender<MsgId>,
+    ) -> (ResponseSender, Self) {
+        let (sender, receiver) = oneshot::channel();
+
+        let response = Self {
+            msg_id,
+            receiver,
+            cancel,
+            received: false,
+            _marker: PhantomData,
+        };
+
+        (sender, response)
+    }
+
+    /// Identifier of the message the function was first sent in.
+    ///
+    /// Rejected messages are resent with new identifiers.
+    #[inline]
+    pub fn msg_id(&self) -> MsgId {
+        self.msg_id
+    }
+}
+
+impl<X: tl::Function> Future for Response<X> {
+    type Output = Result<X::Response, InvokeError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let result = ready!(Pin::new(&mut self.receiver).poll(cx));
+
+        self.received = true;
+
+        let Ok(result) = result else {
+            return Poll::Ready(Err(InvokeError::Dropped));
+        };
+
+        let buffer = result?;
+
+        Poll::Ready(match tl::de(&buffer) {
+            Ok(response) => Ok(response),
+            Err(source) => Err(InvokeError::Deserialization { source, buffer }),
+        })
+    }
+}
+
+impl<X: tl::Function> Drop for Response<X> {
+    fn drop(&mut self) {
+        if !self.received {
+            let _ = self.cancel.send(self.msg_id);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::mtproto::Salt;
+use crate::tl;
+
+use tl::mtproto::types::FutureSalt;
+
+/// Seconds before the last known salt expires to request more.
+const REFILL_MARGIN: i32 = 60 * 60;
+
+/// Server salts by the periods of server time they are valid over.
+///
+/// ---
+/// https://core.telegram.org/mtproto/service_messages#request-for-several-future-salts
+pub(super) struct Salts {
+    /// Sorted by `valid_since`.
+    salts: Vec<FutureSalt>,
+}
+
+impl Salts {
+    pub fn new() -> Self {
+        Self { salts: Vec::new() }
+    }
+
+    pub fn clear(&mut self) {
+        self.salts.clear();
+    }
+
+    pub fn set(&mut self, mut salts: Vec<FutureSalt>) {
+        salts.sort_by_key(|salt| salt.valid_since);
+
+        self.salts = salts;
+    }
+
+    /// Returns the most recent salt valid at the server time, forgetting the expired ones.
+    pub fn get(&mut self, now: i32) -> Option<Salt> {
+        self.salts.retain(|salt| salt.valid_until > now);
+
+        self.salts
+            .iter()
+            .rfind(|salt| salt.valid_since <= now)
+            .map(|salt| salt.salt)
+    }
+
+    /// Returns `true` if the known salts are about to run out.
+    pub fn needs_refill(&self, now: i32) -> bool {
+        self.salts
+            .last()
+            .is_none_or(|salt| salt.valid_until - now < REFILL_MARGIN)
+    }
+}
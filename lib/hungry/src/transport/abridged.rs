@@ -0,0 +1,262 @@
+use std::mem;
+use std::ops::{ControlFlow, RangeFrom};
+
+use bytes::BytesMut;
+
+use crate::transport::{
+    Packet, QuickAck, Tagged, Transport, TransportError, TransportRead, TransportWrite, Unpack,
+};
+use crate::{Envelope, EnvelopeSize};
+
+/// The lightest protocol available.
+///
+/// The length is encoded in 4-byte units: a single byte for lengths
+/// below `0x7f`, otherwise `0x7f` followed by 3 bytes (little endian).
+///
+/// ---
+/// https://core.telegram.org/mtproto/mtproto-transports#abridged
+#[derive(Default)]
+pub struct Abridged;
+
+pub struct AbridgedRead;
+
+pub struct AbridgedWrite {
+    init: bool,
+}
+
+impl Abridged {
+    /// The first byte sent by the client to select the protocol.
+    pub const INIT: u8 = 0xef;
+}
+
+impl Transport for Abridged {
+    type Read = AbridgedRead;
+    type Write = AbridgedWrite;
+
+    fn split(self) -> (Self::Read, Self::Write) {
+        (AbridgedRead, AbridgedWrite { init: true })
+    }
+}
+
+impl Tagged for Abridged {
+    const TAG: [u8; 4] = [0xef; 4];
+
+    fn split_without_init(self) -> (Self::Read, Self::Write) {
+        (AbridgedRead, AbridgedWrite { init: false })
+    }
+}
+
+impl EnvelopeSize for Abridged {
+    // Init byte and the longest length prefix take 5 bytes;
+    // the rest keeps the payload aligned to 4 bytes.
+    const HEADER: usize = 8;
+    const FOOTER: usize = 0;
+}
+
+impl TransportRead for AbridgedRead {
+    type Transport = Abridged;
+
+    const DEFAULT_BUF_LEN: usize = 1;
+
+    fn unpack(&mut self, buffer: &mut [u8]) -> ControlFlow<Result<Unpack, TransportError>, usize> {
+        let (offset, len) = match buffer[0] {
+            0x80.. => {
+                if buffer.len() < 4 {
+                    return ControlFlow::Continue(4);
+                }
+
+                let token = u32::from_be_bytes(buffer[0..4].try_into().unwrap());
+
+                return ControlFlow::Break(Ok(Unpack::QuickAck(QuickAck { token, len: 4 })));
+            }
+            0x7f => {
+                if buffer.len() < 4 {
+                    return ControlFlow::Continue(4);
+                }
+
+                let len = u32::from_le_bytes([buffer[1], buffer[2], buffer[3], 0]);
+
+                (4, len as usize * 4)
+            }
+            len => (1, len as usize * 4),
+        };
+
+        if len == 0 {
+            return ControlFlow::Break(Err(TransportError::BadLen(0)));
+        }
+
+        if buffer.len() < offset + len {
+            return ControlFlow::Continue(offset + len);
+        }
+
+        if len == 4 {
+            let code = i32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap());
+
+            if code < 0 {
+                return ControlFlow::Break(Err(TransportError::Status((-code).into())));
+            }
+
+            return ControlFlow::Break(Err(TransportError::BadLen(4)));
+        }
+
+        let data = if offset == 1 {
+            // Move the payload to keep it aligned to 4 bytes.
+            buffer.copy_within(1..1 + len, 0);
+
+            0..len
+        } else {
+            offset..offset + len
+        };
+
+        ControlFlow::Break(Ok(Unpack::Packet(Packet { data })))
+    }
+}
+
+impl TransportWrite for AbridgedWrite {
+    type Transport = Abridged;
+
+    fn pack(
+        &mut self,
+        buffer: &mut BytesMut,
+        mut envelope: Envelope<Self::Transport>,
+        quick_ack: bool,
+    ) -> RangeFrom<usize> {
+        let excess = envelope.adapt(buffer);
+        let (h, _) = envelope.buffers();
+
+        assert!(
+            buffer.len().is_multiple_of(4),
+            "abridged packet length must be divisible by 4"
+        );
+
+        let len = buffer.len() / 4;
+
+        let mut start = if len < 0x7f {
+            h[7] = len as u8;
+
+            7
+        } else {
+            assert!(len < 1 << 24, "abridged packet is too large");
+
+            h[4] = 0x7f;
+            h[5..8].copy_from_slice(&(len as u32).to_le_bytes()[..3]);
+
+            4
+        };
+
+        if quick_ack {
+            h[start] |= 0x80;
+        }
+
+        if mem::take(&mut self.init) {
+            start -= 1;
+
+            h[start] = Abridged::INIT;
+        }
+
+        envelope.unsplit(buffer, excess);
+
+        start..
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::transport::TransportStatus;
+
+    fn pack(write: &mut AbridgedWrite, payload: &[u8]) -> Vec<u8> {
+        let mut buffer = BytesMut::with_capacity(Abridged::HEADER + payload.len());
+
+        let envelope = Envelope::split(&mut buffer);
+        buffer.extend_from_slice(payload);
+
+        let range = write.pack(&mut buffer, envelope, false);
+
+        buffer[range].to_vec()
+    }
+
+    fn unpack(read: &mut AbridgedRead, packet: &mut [u8]) -> Vec<u8> {
+        let mut end = AbridgedRead::DEFAULT_BUF_LEN;
+
+        loop {
+            match read.unpack(&mut packet[..end]) {
+                ControlFlow::Continue(len) => end = len,
+                ControlFlow::Break(Ok(Unpack::Packet(Packet { data }))) => {
+                    assert_eq!(end, packet.len());
+
+                    return packet[data].to_vec();
+                }
+                ControlFlow::Break(unpack) => panic!("unexpected unpack: {unpack:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_transport_abridged_short() {
+        let (mut read, mut write) = Abridged.split();
+
+        let payload = [7u8; 20];
+
+        let mut packet = pack(&mut write, &payload);
+        assert_eq!(packet[..2], [0xef, 5]);
+        assert_eq!(unpack(&mut read, &mut packet[1..]), payload);
+
+        let mut packet = pack(&mut write, &payload);
+        assert_eq!(packet[..1], [5]);
+        assert_eq!(unpack(&mut read, &mut packet), payload);
+    }
+
+    #[test]
+    fn test_transport_abridged_long() {
+        let (mut read, mut write) = Abridged.split();
+
+        let payload = vec![7u8; 0x7f * 4 + 4];
+
+        let mut packet = pack(&mut write, &payload);
+        assert_eq!(packet[..5], [0xef, 0x7f, 0x80, 0, 0]);
+        assert_eq!(unpack(&mut read, &mut packet[1..]), payload);
+    }
+
+    #[test]
+    fn test_transport_abridged_quick_ack() {
+        let (mut read, mut write) = Abridged.split_without_init();
+
+        let mut buffer = BytesMut::with_capacity(Abridged::HEADER + 20);
+
+        let envelope = Envelope::split(&mut buffer);
+        buffer.extend_from_slice(&[7u8; 20]);
+
+        let range = write.pack(&mut buffer, envelope, true);
+        assert_eq!(buffer[range.start], 0x85);
+
+        let mut packet = [0x81, 0x02, 0x03, 0x04];
+
+        let unpack = read.unpack(&mut packet);
+
+        assert!(matches!(
+            unpack,
+            ControlFlow::Break(Ok(Unpack::QuickAck(QuickAck {
+                token: 0x81020304,
+                len: 4
+            })))
+        ));
+    }
+
+    #[test]
+    fn test_transport_abridged_status() {
+        let mut read = AbridgedRead;
+
+        let mut packet = [1, 0x6c, 0xfe, 0xff, 0xff];
+
+        let unpack = read.unpack(&mut packet);
+
+        assert!(matches!(
+            unpack,
+            ControlFlow::Break(Err(TransportError::Status(
+                TransportStatus::AuthKeyNotFound
+            )))
+        ));
+    }
+}
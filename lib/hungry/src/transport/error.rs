@@ -1,12 +1,22 @@
 use std::fmt;
 
+use crate::transport::TransportStatus;
+
 #[derive(Debug)]
 pub enum TransportError {
-    QuickAck,
-    Status(i32),
+    Status(TransportStatus),
     BadLen(i32),
-    BadCrc { received: u32, computed: u32 },
-    BadSeq { received: i32, expected: i32 },
+    BadCrc {
+        received: u32,
+        computed: u32,
+    },
+    BadSeq {
+        received: i32,
+        expected: i32,
+    },
+    BadHttp,
+    /// Unsuccessful HTTP response without the error code in the body.
+    HttpStatus(u16),
 }
 
 impl fmt::Display for TransportError {
@@ -16,8 +26,7 @@ impl fmt::Display for TransportError {
         f.write_str("transport error: ")?;
 
         match self {
-            QuickAck => write!(f, "quick ack is not supported"),
-            Status(code) => write!(f, "status code: {code}"),
+            Status(code) => write!(f, "status: {code}"),
             BadLen(len) => write!(f, "bad len: {len}"),
             BadCrc {
                 received: r,
@@ -27,6 +36,8 @@ impl fmt::Display for TransportError {
                 received: r,
                 expected: e,
             } => write!(f, "bad seq: received {r}, expected {e}"),
+            BadHttp => write!(f, "bad http response"),
+            HttpStatus(status) => write!(f, "http status: {status}"),
         }
     }
 }
@@ -2,7 +2,9 @@ use std::ops::{ControlFlow, RangeFrom};
 
 use bytes::BytesMut;
 
-use crate::transport::{Packet, Transport, TransportError, TransportRead, TransportWrite, Unpack};
+use crate::transport::{
+    Packet, QuickAck, Transport, TransportError, TransportRead, TransportWrite, Unpack,
+};
 use crate::{Envelope, EnvelopeSize, crypto};
 
 #[derive(Default)]
@@ -25,6 +27,10 @@ impl Transport for Full {
     }
 }
 
+/// Negative lengths down to this one are status codes,
+/// the lower ones are quick ack tokens with the most significant bit set.
+const MIN_STATUS: i32 = -0xffff;
+
 impl EnvelopeSize for Full {
     const HEADER: usize = 8;
     const FOOTER: usize = 4;
@@ -41,7 +47,14 @@ impl TransportRead for FullRead {
         }
 
         let len = match i32::from_le_bytes(buffer[0..4].try_into().unwrap()) {
-            len @ ..0 => return ControlFlow::Break(Err(TransportError::Status(-len))),
+            code @ MIN_STATUS..0 => {
+                return ControlFlow::Break(Err(TransportError::Status((-code).into())));
+            }
+            token @ ..0 => {
+                let token = token as u32;
+
+                return ControlFlow::Break(Ok(Unpack::QuickAck(QuickAck { token, len: 4 })));
+            }
             len @ 0..12 => return ControlFlow::Break(Err(TransportError::BadLen(len))),
             len => len as usize,
         };
@@ -82,11 +95,13 @@ impl TransportWrite for FullWrite {
         &mut self,
         buffer: &mut BytesMut,
         mut envelope: Envelope<Self::Transport>,
+        quick_ack: bool,
     ) -> RangeFrom<usize> {
         let excess = envelope.adapt(buffer);
         let (h, f) = envelope.buffers();
 
-        let len = 4 + 4 + buffer.len() as i32 + 4;
+        let len = 4 + 4 + buffer.len() as u32 + 4;
+        let len = if quick_ack { len | 0x80000000 } else { len };
 
         h[0..4].copy_from_slice(&len.to_le_bytes());
         h[4..8].copy_from_slice(&self.seq.to_le_bytes());
@@ -102,3 +117,87 @@ impl TransportWrite for FullWrite {
         0..
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::transport::TransportStatus;
+
+    #[test]
+    fn test_transport_full() {
+        let (mut read, mut write) = Full.split();
+
+        let payload = [7u8; 20];
+
+        for seq in 0..2 {
+            let mut buffer = BytesMut::with_capacity(Full::HEADER + payload.len() + Full::FOOTER);
+
+            let envelope = Envelope::split(&mut buffer);
+            buffer.extend_from_slice(&payload);
+
+            let range = write.pack(&mut buffer, envelope, false);
+            let packet = &mut buffer[range];
+
+            assert_eq!(packet[..8], [32, 0, 0, 0, seq, 0, 0, 0]);
+
+            let ControlFlow::Break(Ok(Unpack::Packet(Packet { data }))) = read.unpack(packet)
+            else {
+                panic!("unexpected unpack");
+            };
+
+            assert_eq!(packet[data], payload);
+        }
+    }
+
+    #[test]
+    fn test_transport_full_quick_ack() {
+        let (mut read, mut write) = Full.split();
+
+        let mut buffer = BytesMut::with_capacity(Full::HEADER + 20 + Full::FOOTER);
+
+        let envelope = Envelope::split(&mut buffer);
+        buffer.extend_from_slice(&[7u8; 20]);
+
+        let range = write.pack(&mut buffer, envelope, true);
+        assert_eq!(buffer[range][..4], [32, 0, 0, 0x80]);
+
+        let mut packet = [0x04, 0x03, 0x02, 0x81];
+
+        let unpack = read.unpack(&mut packet);
+
+        assert!(matches!(
+            unpack,
+            ControlFlow::Break(Ok(Unpack::QuickAck(QuickAck {
+                token: 0x81020304,
+                len: 4
+            })))
+        ));
+
+        let mut packet = 0x80000000u32.to_le_bytes();
+
+        assert!(matches!(
+            read.unpack(&mut packet),
+            ControlFlow::Break(Ok(Unpack::QuickAck(QuickAck {
+                token: 0x80000000,
+                len: 4
+            })))
+        ));
+    }
+
+    #[test]
+    fn test_transport_full_status() {
+        let (mut read, _) = Full.split();
+
+        let mut packet = (-404i32).to_le_bytes();
+
+        let unpack = read.unpack(&mut packet);
+
+        assert!(matches!(
+            unpack,
+            ControlFlow::Break(Err(TransportError::Status(
+                TransportStatus::AuthKeyNotFound
+            )))
+        ));
+    }
+}
@@ -0,0 +1,486 @@
+use std::ops::{ControlFlow, Range, RangeFrom};
+
+use bytes::BytesMut;
+
+use crate::transport::{Packet, Transport, TransportError, TransportRead, TransportWrite, Unpack};
+use crate::{Envelope, EnvelopeSize};
+
+/// Each packet is sent as the body of an HTTP/1.1 `POST /api` request;
+/// every response body contains a single packet.
+///
+/// The server can only send a packet in a response to a request,
+/// so `http_wait` must be sent to receive server-initiated messages.
+/// It is not sent automatically: the caller must keep one pending with
+/// [`Sender::http_wait`], sending the next one once a packet is received.
+///
+/// [`Sender::http_wait`]: crate::Sender::http_wait
+///
+/// ---
+/// https://core.telegram.org/mtproto/mtproto-transports#http
+pub struct Http {
+    host: String,
+}
+
+pub struct HttpRead {
+    /// Status code and the body of the response with a parsed header.
+    body: Option<(u16, Range<usize>)>,
+    /// Length of the buffer already searched for the end of the header.
+    scanned: usize,
+    /// Length of the last response.
+    consumed: usize,
+}
+
+pub struct HttpWrite {
+    /// Request header up to the value of `Content-Length`.
+    head: Vec<u8>,
+}
+
+impl Http {
+    /// Longest response header accepted.
+    const MAX_RESPONSE_HEADER_LEN: usize = 8192;
+
+    pub fn new(host: impl Into<String>) -> Self {
+        let host = host.into();
+
+        assert!(host.len() <= 255, "host is too long");
+
+        Self { host }
+    }
+}
+
+impl Transport for Http {
+    type Read = HttpRead;
+    type Write = HttpWrite;
+
+    const QUICK_ACK: bool = false;
+
+    fn split(self) -> (Self::Read, Self::Write) {
+        let head = format!(
+            "POST /api HTTP/1.1\r\n\
+            Host: {}\r\n\
+            Connection: keep-alive\r\n\
+            Content-Type: application/octet-stream\r\n\
+            Content-Length: ",
+            self.host
+        );
+
+        let write = HttpWrite {
+            head: head.into_bytes(),
+        };
+
+        let read = HttpRead {
+            body: None,
+            scanned: 0,
+            consumed: 0,
+        };
+
+        (read, write)
+    }
+}
+
+impl EnvelopeSize for Http {
+    // The request header is variable in length, it is aligned to the end.
+    const HEADER: usize = 512;
+    const FOOTER: usize = 0;
+}
+
+/// Parse the response header, without the trailing empty line.
+fn parse_header(header: &[u8]) -> Option<(u16, usize)> {
+    let header = str::from_utf8(header).ok()?;
+
+    let mut lines = header.split("\r\n");
+
+    let status = lines.next()?.strip_prefix("HTTP/1.")?.get(2..5)?;
+    let status = status.parse().ok()?;
+
+    let len = lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+
+        if !name.trim().eq_ignore_ascii_case("content-length") {
+            return None;
+        }
+
+        value.trim().parse().ok()
+    })?;
+
+    Some((status, len))
+}
+
+impl TransportRead for HttpRead {
+    type Transport = Http;
+
+    // Shortest status line: `HTTP/1.1 200`.
+    const DEFAULT_BUF_LEN: usize = 12;
+
+    // The length is only known from the header, and the body may be empty.
+    const READ_AHEAD: usize = 4096;
+
+    fn unpack(&mut self, buffer: &mut [u8]) -> ControlFlow<Result<Unpack, TransportError>, usize> {
+        const END: &[u8] = b"\r\n\r\n";
+
+        let (status, body) = match &self.body {
+            Some(body) => body.clone(),
+            None => {
+                let from = self.scanned.saturating_sub(END.len() - 1);
+
+                let Some(pos) = buffer[from..]
+                    .windows(END.len())
+                    .position(|window| window == END)
+                else {
+                    if buffer.len() > Http::MAX_RESPONSE_HEADER_LEN {
+                        return self.bad_http(buffer);
+                    }
+
+                    self.scanned = buffer.len();
+
+                    return ControlFlow::Continue(buffer.len() + 1);
+                };
+
+                let pos = from + pos;
+
+                let Some((status, len)) = parse_header(&buffer[..pos]) else {
+                    return self.bad_http(buffer);
+                };
+
+                self.scanned = 0;
+
+                let start = pos + END.len();
+
+                self.body.insert((status, start..start + len)).clone()
+            }
+        };
+
+        if buffer.len() < body.end {
+            return ControlFlow::Continue(body.end);
+        }
+
+        self.body = None;
+        self.consumed = body.end;
+
+        // Negative error code sent instead of a packet.
+        let code = match body.len() {
+            4 => Some(i32::from_le_bytes(buffer[body.clone()].try_into().unwrap())),
+            _ => None,
+        };
+
+        if status != 200 {
+            return ControlFlow::Break(Err(match code {
+                Some(code @ ..0) => TransportError::Status((-code).into()),
+                // Not necessarily sent by the server, e.g. by a proxy.
+                _ => TransportError::HttpStatus(status),
+            }));
+        }
+
+        if let Some(code) = code {
+            return ControlFlow::Break(Err(match code {
+                ..0 => TransportError::Status((-code).into()),
+                _ => TransportError::BadLen(4),
+            }));
+        }
+
+        if body.len() < 4 {
+            return ControlFlow::Break(Err(TransportError::BadLen(body.len() as i32)));
+        }
+
+        let len = body.len();
+
+        // Move the payload to keep it aligned to 4 bytes.
+        buffer.copy_within(body, 0);
+
+        ControlFlow::Break(Ok(Unpack::Packet(Packet { data: 0..len })))
+    }
+
+    fn consumed(&self) -> Option<usize> {
+        Some(self.consumed)
+    }
+}
+
+impl HttpRead {
+    /// The connection cannot be used anymore.
+    fn bad_http(&mut self, buffer: &[u8]) -> ControlFlow<Result<Unpack, TransportError>, usize> {
+        self.scanned = 0;
+        self.consumed = buffer.len();
+
+        ControlFlow::Break(Err(TransportError::BadHttp))
+    }
+}
+
+impl TransportWrite for HttpWrite {
+    type Transport = Http;
+
+    fn pack(
+        &mut self,
+        buffer: &mut BytesMut,
+        mut envelope: Envelope<Self::Transport>,
+        _quick_ack: bool, // not supported
+    ) -> RangeFrom<usize> {
+        let excess = envelope.adapt(buffer);
+        let (h, _) = envelope.buffers();
+
+        let len = buffer.len().to_string();
+
+        let start = Http::HEADER - self.head.len() - len.len() - 4;
+
+        let mut pos = start;
+
+        for part in [self.head.as_slice(), len.as_bytes(), b"\r\n\r\n"] {
+            h[pos..pos + part.len()].copy_from_slice(part);
+
+            pos += part.len();
+        }
+
+        envelope.unsplit(buffer, excess);
+
+        start..
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::poll_fn;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use crate::reader::{Reader, ReaderError};
+    use crate::transport::TransportStatus;
+    use crate::{mtproto, plain, tl};
+
+    /// Read an HTTP request with the body, as the server would.
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> (String, Vec<u8>) {
+        let mut request = Vec::new();
+
+        while !request.ends_with(b"\r\n\r\n") {
+            request.push(stream.read_u8().await.unwrap());
+        }
+
+        let header = String::from_utf8(request).unwrap();
+
+        let len = header
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body).await.unwrap();
+
+        (header, body)
+    }
+
+    #[tokio::test]
+    async fn test_transport_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let (header, body) = read_request(&mut stream).await;
+
+            assert!(header.starts_with("POST /api HTTP/1.1\r\n"));
+            assert!(header.contains(&format!("Host: {addr}\r\n")));
+
+            // Plain message: auth_key_id, message_id, message_data_length.
+            assert_eq!(body[0..8], [0; 8]);
+            assert_eq!(body[16..20], 12u32.to_le_bytes());
+            assert_eq!(body[20..24], 0x7abe77ecu32.to_le_bytes());
+
+            let ping_id = &body[24..32];
+
+            let mut response = Vec::new();
+            response.extend_from_slice(&[0; 8]);
+            response.extend_from_slice(&5i64.to_le_bytes());
+            response.extend_from_slice(&20u32.to_le_bytes());
+            response.extend_from_slice(&0x347773c5u32.to_le_bytes());
+            response.extend_from_slice(&body[8..16]);
+            response.extend_from_slice(ping_id);
+
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: keep-alive\r\n\r\n",
+                response.len()
+            );
+
+            stream.write_all(header.as_bytes()).await.unwrap();
+            stream.write_all(&response).await.unwrap();
+
+            // Only the error code is a transport error.
+            read_request(&mut stream).await;
+
+            let header = "HTTP/1.1 429 Too Many Requests\r\nContent-Length: 4\r\n\r\n";
+
+            stream.write_all(header.as_bytes()).await.unwrap();
+            stream.write_all(&(-429i32).to_le_bytes()).await.unwrap();
+
+            read_request(&mut stream).await;
+
+            let header = "HTTP/1.1 429 Too Many Requests\r\nContent-Length: 0\r\n\r\n";
+
+            stream.write_all(header.as_bytes()).await.unwrap();
+
+            read_request(&mut stream).await;
+
+            let header = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+
+            stream.write_all(header.as_bytes()).await.unwrap();
+
+            read_request(&mut stream).await;
+
+            let header = "HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\n\r\n";
+
+            stream.write_all(header.as_bytes()).await.unwrap();
+            stream.write_all(&(-404i32).to_le_bytes()).await.unwrap();
+        });
+
+        let (r, w) = tokio::net::TcpStream::connect(addr)
+            .await
+            .unwrap()
+            .into_split();
+
+        let (mut reader, mut writer) = crate::init(
+            Http::new(addr.to_string()),
+            r,
+            BytesMut::with_capacity(1024),
+            w,
+        );
+
+        let func = tl::mtproto::funcs::Ping { ping_id: 123 };
+
+        let mut buffer = BytesMut::with_capacity(1024);
+
+        let transport = Envelope::split(&mut buffer);
+        let mtp = Envelope::split(&mut buffer);
+
+        let (message_id, pong) = plain::send(
+            &mut reader,
+            &mut writer,
+            &func,
+            &mut buffer,
+            transport,
+            mtp,
+            4,
+        )
+        .await
+        .unwrap();
+
+        let tl::mtproto::enums::Pong::Pong(pong) = pong;
+
+        assert_eq!(message_id, 5);
+        assert_eq!(pong.msg_id, 4);
+        assert_eq!(pong.ping_id, 123);
+
+        let expected = [
+            (429, Some(TransportStatus::TransportFlood)),
+            (429, None),
+            (404, None),
+            (404, Some(TransportStatus::AuthKeyNotFound)),
+        ];
+
+        for (i, (status, expected)) in expected.into_iter().enumerate() {
+            buffer.clear();
+
+            let transport = Envelope::split(&mut buffer);
+            let mtp: mtproto::PlainEnvelope = Envelope::split(&mut buffer);
+
+            let err = plain::send(
+                &mut reader,
+                &mut writer,
+                &func,
+                &mut buffer,
+                transport,
+                mtp,
+                8 + 4 * i as i64,
+            )
+            .await
+            .unwrap_err();
+
+            let plain::Error::Reader(crate::reader::ReaderError::Transport(err)) = err else {
+                panic!("unexpected error: {err}");
+            };
+
+            match expected {
+                Some(expected) => {
+                    assert!(matches!(err, TransportError::Status(s) if s == expected))
+                }
+                None => assert!(matches!(err, TransportError::HttpStatus(s) if s == status)),
+            }
+        }
+
+        server.await.unwrap();
+    }
+
+    /// Counts the reads from the wrapped reader.
+    struct CountReads<'a> {
+        data: &'a [u8],
+        reads: usize,
+    }
+
+    impl tokio::io::AsyncRead for CountReads<'_> {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.reads += 1;
+
+            std::pin::Pin::new(&mut self.data).poll_read(cx, buf)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transport_http_read_ahead() {
+        let mut data = Vec::new();
+
+        for (i, body) in [[1u8; 8], [2; 8]].iter().enumerate() {
+            let status = if i == 0 { "200 OK" } else { "404 Not Found" };
+
+            let header = format!(
+                "HTTP/1.1 {status}\r\nConnection: keep-alive\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            );
+
+            data.extend_from_slice(header.as_bytes());
+            data.extend_from_slice(body);
+        }
+
+        data.extend_from_slice(b"HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\n\r\n");
+        data.extend_from_slice(&(-404i32).to_le_bytes());
+
+        let mut driver = CountReads {
+            data: &data,
+            reads: 0,
+        };
+
+        let (read, _) = Http::new("localhost").split();
+        let mut reader: Reader<_, Http> =
+            Reader::new(&mut driver, read, BytesMut::with_capacity(1024));
+
+        let unpack = poll_fn(|cx| reader.poll(cx)).await;
+        let ControlFlow::Continue(Ok(Unpack::Packet(packet))) = unpack else {
+            panic!("unexpected result: {unpack:?}");
+        };
+
+        assert_eq!(reader.buffer().split()[packet.data], [1; 8]);
+
+        // The following responses were read along with the first one.
+        let unpack = poll_fn(|cx| reader.poll(cx)).await;
+        assert!(matches!(
+            unpack,
+            ControlFlow::Continue(Err(ReaderError::Transport(TransportError::HttpStatus(404))))
+        ));
+
+        let unpack = poll_fn(|cx| reader.poll(cx)).await;
+        assert!(matches!(
+            unpack,
+            ControlFlow::Continue(Err(ReaderError::Transport(TransportError::Status(
+                TransportStatus::AuthKeyNotFound
+            ))))
+        ));
+
+        drop(reader);
+        assert_eq!(driver.reads, 1);
+    }
+}
@@ -0,0 +1,242 @@
+use std::mem;
+use std::ops::{ControlFlow, Range, RangeFrom};
+
+use bytes::BytesMut;
+
+use crate::transport::{
+    Packet, QuickAck, Tagged, Transport, TransportError, TransportRead, TransportWrite, Unpack,
+};
+use crate::{Envelope, EnvelopeSize};
+
+/// Each packet is prefixed with its length as a 4-byte little endian integer.
+///
+/// ---
+/// https://core.telegram.org/mtproto/mtproto-transports#intermediate
+#[derive(Default)]
+pub struct Intermediate;
+
+pub struct IntermediateRead;
+
+pub struct IntermediateWrite {
+    init: bool,
+}
+
+impl Intermediate {
+    /// The first 4 bytes sent by the client to select the protocol.
+    pub const INIT: [u8; 4] = [0xee; 4];
+}
+
+impl Transport for Intermediate {
+    type Read = IntermediateRead;
+    type Write = IntermediateWrite;
+
+    fn split(self) -> (Self::Read, Self::Write) {
+        (IntermediateRead, IntermediateWrite { init: true })
+    }
+}
+
+impl Tagged for Intermediate {
+    const TAG: [u8; 4] = Self::INIT;
+
+    fn split_without_init(self) -> (Self::Read, Self::Write) {
+        (IntermediateRead, IntermediateWrite { init: false })
+    }
+}
+
+impl EnvelopeSize for Intermediate {
+    const HEADER: usize = 8;
+    const FOOTER: usize = 0;
+}
+
+/// Unpack the length prefix shared by the intermediate transports.
+pub(super) fn unpack_len(
+    buffer: &[u8],
+) -> ControlFlow<Result<Result<Range<usize>, QuickAck>, TransportError>, usize> {
+    if buffer.len() < 4 {
+        return ControlFlow::Continue(4);
+    }
+
+    let len = match u32::from_le_bytes(buffer[0..4].try_into().unwrap()) {
+        token @ 0x80000000.. => {
+            return ControlFlow::Break(Ok(Err(QuickAck { token, len: 4 })));
+        }
+        len @ 0..4 => return ControlFlow::Break(Err(TransportError::BadLen(len as i32))),
+        len => len as usize,
+    };
+
+    if buffer.len() < 4 + len {
+        return ControlFlow::Continue(4 + len);
+    }
+
+    ControlFlow::Break(Ok(Ok(4..4 + len)))
+}
+
+/// Negative status code sent instead of a packet.
+pub(super) fn status(data: &[u8]) -> Option<i32> {
+    match i32::from_le_bytes(data[0..4].try_into().unwrap()) {
+        code @ ..0 => Some(-code),
+        _ => None,
+    }
+}
+
+/// Write the length prefix shared by the intermediate transports.
+pub(super) fn pack_len(
+    h: &mut [u8],
+    len: usize,
+    quick_ack: bool,
+    init: &mut bool,
+    tag: [u8; 4],
+) -> usize {
+    let len = if quick_ack {
+        len as u32 | 0x80000000
+    } else {
+        len as u32
+    };
+
+    h[4..8].copy_from_slice(&len.to_le_bytes());
+
+    if mem::take(init) {
+        h[0..4].copy_from_slice(&tag);
+
+        0
+    } else {
+        4
+    }
+}
+
+impl TransportRead for IntermediateRead {
+    type Transport = Intermediate;
+
+    const DEFAULT_BUF_LEN: usize = 4;
+
+    fn unpack(&mut self, buffer: &mut [u8]) -> ControlFlow<Result<Unpack, TransportError>, usize> {
+        let data = match unpack_len(buffer) {
+            ControlFlow::Break(Ok(Ok(data))) => data,
+            ControlFlow::Break(Ok(Err(quick_ack))) => {
+                return ControlFlow::Break(Ok(Unpack::QuickAck(quick_ack)));
+            }
+            ControlFlow::Break(Err(err)) => return ControlFlow::Break(Err(err)),
+            ControlFlow::Continue(len) => return ControlFlow::Continue(len),
+        };
+
+        if data.len() == 4 {
+            return ControlFlow::Break(Err(match status(&buffer[data]) {
+                Some(code) => TransportError::Status(code.into()),
+                None => TransportError::BadLen(4),
+            }));
+        }
+
+        ControlFlow::Break(Ok(Unpack::Packet(Packet { data })))
+    }
+}
+
+impl TransportWrite for IntermediateWrite {
+    type Transport = Intermediate;
+
+    fn pack(
+        &mut self,
+        buffer: &mut BytesMut,
+        mut envelope: Envelope<Self::Transport>,
+        quick_ack: bool,
+    ) -> RangeFrom<usize> {
+        let excess = envelope.adapt(buffer);
+        let (h, _) = envelope.buffers();
+
+        let start = pack_len(
+            h,
+            buffer.len(),
+            quick_ack,
+            &mut self.init,
+            Intermediate::INIT,
+        );
+
+        envelope.unsplit(buffer, excess);
+
+        start..
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::transport::TransportStatus;
+
+    fn pack(write: &mut IntermediateWrite, payload: &[u8], quick_ack: bool) -> Vec<u8> {
+        let mut buffer = BytesMut::with_capacity(Intermediate::HEADER + payload.len());
+
+        let envelope = Envelope::split(&mut buffer);
+        buffer.extend_from_slice(payload);
+
+        let range = write.pack(&mut buffer, envelope, quick_ack);
+
+        buffer[range].to_vec()
+    }
+
+    fn unpack(read: &mut IntermediateRead, packet: &mut [u8]) -> Vec<u8> {
+        let mut end = IntermediateRead::DEFAULT_BUF_LEN;
+
+        loop {
+            match read.unpack(&mut packet[..end]) {
+                ControlFlow::Continue(len) => end = len,
+                ControlFlow::Break(Ok(Unpack::Packet(Packet { data }))) => {
+                    assert_eq!(end, packet.len());
+
+                    return packet[data].to_vec();
+                }
+                ControlFlow::Break(unpack) => panic!("unexpected unpack: {unpack:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_transport_intermediate() {
+        let (mut read, mut write) = Intermediate.split();
+
+        let payload = [7u8; 20];
+
+        let mut packet = pack(&mut write, &payload, false);
+        assert_eq!(packet[..8], [0xee, 0xee, 0xee, 0xee, 20, 0, 0, 0]);
+        assert_eq!(unpack(&mut read, &mut packet[4..]), payload);
+
+        let mut packet = pack(&mut write, &payload, false);
+        assert_eq!(packet[..4], [20, 0, 0, 0]);
+        assert_eq!(unpack(&mut read, &mut packet), payload);
+    }
+
+    #[test]
+    fn test_transport_intermediate_quick_ack() {
+        let (mut read, mut write) = Intermediate.split_without_init();
+
+        let packet = pack(&mut write, &[7u8; 20], true);
+        assert_eq!(packet[..4], [20, 0, 0, 0x80]);
+
+        let mut packet = [0x04, 0x03, 0x02, 0x81];
+
+        let unpack = read.unpack(&mut packet);
+
+        assert!(matches!(
+            unpack,
+            ControlFlow::Break(Ok(Unpack::QuickAck(QuickAck {
+                token: 0x81020304,
+                len: 4
+            })))
+        ));
+    }
+
+    #[test]
+    fn test_transport_intermediate_status() {
+        let mut read = IntermediateRead;
+
+        let mut packet = [4, 0, 0, 0, 0x6c, 0xfe, 0xff, 0xff];
+
+        let unpack = read.unpack(&mut packet);
+
+        assert!(matches!(
+            unpack,
+            ControlFlow::Break(Err(TransportError::Status(
+                TransportStatus::AuthKeyNotFound
+            )))
+        ));
+    }
+}
@@ -1,5 +1,11 @@
+mod abridged;
 mod error;
 mod full;
+mod http;
+mod intermediate;
+mod obfuscated;
+mod padded_intermediate;
+mod status;
 
 use std::ops::{ControlFlow, Range, RangeFrom};
 
@@ -7,9 +13,21 @@ use bytes::BytesMut;
 
 use crate::{Envelope, EnvelopeSize};
 
+pub use abridged::Abridged;
 pub use error::TransportError;
 pub use full::Full;
+pub use http::Http;
+pub use intermediate::Intermediate;
+pub use obfuscated::Obfuscated;
+pub use padded_intermediate::PaddedIntermediate;
+pub use status::TransportStatus;
 
+/// Sent by the server right after receiving a packet with the quick ack
+/// bit set. The `token` is the first 32 bits of `msg_key_large` of the
+/// received packet with the most significant bit set.
+///
+/// ---
+/// https://core.telegram.org/mtproto/mtproto-transports#quick-ack
 #[derive(Debug, Eq, PartialEq)]
 pub struct QuickAck {
     pub token: u32,
@@ -31,25 +49,59 @@ pub trait Transport: EnvelopeSize + Unpin {
     type Read: TransportRead<Transport = Self>;
     type Write: TransportWrite<Transport = Self>;
 
+    /// Whether the server can be requested to send [`QuickAck`].
+    const QUICK_ACK: bool = true;
+
     #[must_use]
     fn split(self) -> (Self::Read, Self::Write);
 }
 
+/// Transports which can be wrapped into [`Obfuscated`].
+pub trait Tagged: Transport {
+    /// Protocol tag placed into the obfuscation init payload.
+    const TAG: [u8; 4];
+
+    /// Split the transport without sending its own init,
+    /// since the tag is sent by the wrapping transport instead.
+    #[must_use]
+    fn split_without_init(self) -> (Self::Read, Self::Write);
+}
+
 pub trait TransportRead: Unpin {
     type Transport: Transport;
 
     const DEFAULT_BUF_LEN: usize;
 
+    /// Number of bytes which may be read past the length requested by
+    /// `unpack`, to read in larger chunks. The bytes past the [`consumed`]
+    /// length are kept for the next packet.
+    ///
+    /// [`consumed`]: Self::consumed
+    const READ_AHEAD: usize = 0;
+
     fn unpack(&mut self, buffer: &mut [u8]) -> ControlFlow<Result<Unpack, TransportError>, usize>;
+
+    /// Length of the data the last result of `unpack` was read from,
+    /// if not the whole buffer.
+    fn consumed(&self) -> Option<usize> {
+        None
+    }
 }
 
 pub trait TransportWrite: Unpin {
     type Transport: Transport;
 
+    /// Pack the buffer into the envelope. The returned range starts at the
+    /// first byte to be sent, which may include an init prefix written
+    /// into the unused part of the header before the first packet.
+    ///
+    /// If `quick_ack` is set, the server is requested to send [`QuickAck`]
+    /// as soon as the packet is received, if supported by the transport.
     #[must_use]
     fn pack(
         &mut self,
         buffer: &mut BytesMut,
         envelope: Envelope<Self::Transport>,
+        quick_ack: bool,
     ) -> RangeFrom<usize>;
 }
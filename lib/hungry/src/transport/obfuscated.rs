@@ -0,0 +1,262 @@
+use std::ops::{ControlFlow, RangeFrom};
+
+use bytes::BytesMut;
+
+use crate::crypto::{AesCtr, AesCtrKey, sha256};
+use crate::transport::{Tagged, Transport, TransportError, TransportRead, TransportWrite, Unpack};
+use crate::utils::BytesMutExt;
+use crate::{Envelope, EnvelopeSize};
+
+/// Obfuscation wrapping another transport to prevent
+/// it from being detected by the network filters.
+///
+/// Every byte is encrypted with AES-256-CTR using keys
+/// derived from the random 64-byte init payload sent first.
+///
+/// ---
+/// https://core.telegram.org/mtproto/mtproto-transports#transport-obfuscation
+#[derive(Default)]
+pub struct Obfuscated<T: Tagged> {
+    inner: T,
+    proxy: Option<(i16, [u8; 16])>,
+}
+
+pub struct ObfuscatedRead<T: Tagged> {
+    inner: T::Read,
+    decrypt: AesCtr,
+    pos: usize,
+}
+
+pub struct ObfuscatedWrite<T: Tagged> {
+    inner: T::Write,
+    encrypt: AesCtr,
+    init: Option<[u8; 64]>,
+}
+
+impl<T: Tagged> Obfuscated<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner, proxy: None }
+    }
+
+    /// Connect to the DC through an MTProxy. The keys are mixed with the `secret`.
+    ///
+    /// See [`MtProxySecret::obfuscated`] for the secret parsing.
+    ///
+    /// [`MtProxySecret::obfuscated`]: crate::proxy::MtProxySecret::obfuscated
+    pub fn proxy(inner: T, dc_id: i16, secret: [u8; 16]) -> Self {
+        Self {
+            inner,
+            proxy: Some((dc_id, secret)),
+        }
+    }
+
+    fn key(&self, key: &[u8]) -> AesCtrKey {
+        match &self.proxy {
+            Some((_, secret)) => sha256!(key, secret).into(),
+            None => key.try_into().unwrap(),
+        }
+    }
+
+    /// Generate the init payload satisfying the requirements
+    /// for it to not be confused with another protocol.
+    fn init(&self) -> [u8; 64] {
+        const FORBIDDEN: [[u8; 4]; 7] = [
+            *b"HEAD",
+            *b"POST",
+            *b"GET ",
+            *b"OPTI",
+            [0x16, 0x03, 0x01, 0x02],
+            [0xdd; 4],
+            [0xee; 4],
+        ];
+
+        let mut init = [0; 64];
+
+        loop {
+            getrandom::fill(&mut init).unwrap();
+
+            if init[0] == 0xef
+                || FORBIDDEN.contains(&init[0..4].try_into().unwrap())
+                || init[4..8] == [0; 4]
+            {
+                continue;
+            }
+
+            init[56..60].copy_from_slice(&T::TAG);
+
+            if let Some((dc_id, _)) = self.proxy {
+                init[60..62].copy_from_slice(&dc_id.to_le_bytes());
+            }
+
+            return init;
+        }
+    }
+}
+
+impl<T: Tagged> Transport for Obfuscated<T> {
+    type Read = ObfuscatedRead<T>;
+    type Write = ObfuscatedWrite<T>;
+
+    const QUICK_ACK: bool = T::QUICK_ACK;
+
+    fn split(self) -> (Self::Read, Self::Write) {
+        let mut init = self.init();
+
+        let mut reversed: [u8; 48] = init[8..56].try_into().unwrap();
+        reversed.reverse();
+
+        let mut encrypt = AesCtr::new(&self.key(&init[8..40]), init[40..56].try_into().unwrap());
+
+        let decrypt = AesCtr::new(
+            &self.key(&reversed[0..32]),
+            reversed[32..48].try_into().unwrap(),
+        );
+
+        // Only the last 8 bytes of the init payload are sent encrypted;
+        // the keystream is advanced by the whole payload nevertheless.
+        let mut encrypted = init;
+        encrypt.apply(&mut encrypted);
+        init[56..64].copy_from_slice(&encrypted[56..64]);
+
+        let (read, write) = self.inner.split_without_init();
+
+        let read = ObfuscatedRead {
+            inner: read,
+            decrypt,
+            pos: 0,
+        };
+
+        let write = ObfuscatedWrite {
+            inner: write,
+            encrypt,
+            init: Some(init),
+        };
+
+        (read, write)
+    }
+}
+
+impl<T: Tagged> EnvelopeSize for Obfuscated<T> {
+    const HEADER: usize = 64 + T::HEADER;
+    const FOOTER: usize = T::FOOTER;
+}
+
+impl<T: Tagged> TransportRead for ObfuscatedRead<T> {
+    type Transport = Obfuscated<T>;
+
+    const DEFAULT_BUF_LEN: usize = T::Read::DEFAULT_BUF_LEN;
+
+    fn unpack(&mut self, buffer: &mut [u8]) -> ControlFlow<Result<Unpack, TransportError>, usize> {
+        // Only the bytes read since the previous call are decrypted.
+        self.decrypt.apply(&mut buffer[self.pos..]);
+        self.pos = buffer.len();
+
+        let unpack = self.inner.unpack(buffer);
+
+        if unpack.is_break() {
+            self.pos = 0;
+        }
+
+        unpack
+    }
+}
+
+impl<T: Tagged> TransportWrite for ObfuscatedWrite<T> {
+    type Transport = Obfuscated<T>;
+
+    fn pack(
+        &mut self,
+        buffer: &mut BytesMut,
+        envelope: Envelope<Self::Transport>,
+        quick_ack: bool,
+    ) -> RangeFrom<usize> {
+        let (prefix, envelope) = envelope.narrow::<T>();
+
+        let start = self.inner.pack(buffer, envelope, quick_ack).start;
+
+        assert!(
+            prefix.can_unsplit(buffer),
+            "buffer does not belong to the envelope"
+        );
+
+        buffer.unsplit_reverse(prefix);
+
+        let packet = start + 64;
+
+        self.encrypt.apply(&mut buffer[packet..]);
+
+        match self.init.take() {
+            // The init payload takes the unused space right before the packet.
+            Some(init) => {
+                buffer[start..packet].copy_from_slice(&init);
+
+                start..
+            }
+            None => packet..,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::transport::{Intermediate, Packet};
+
+    #[test]
+    fn test_transport_obfuscated() {
+        let (mut read, mut write) = Obfuscated::new(Intermediate).split();
+
+        let payload = [7u8; 32];
+
+        let mut buffer = BytesMut::with_capacity(Obfuscated::<Intermediate>::HEADER + 32);
+
+        let envelope = Envelope::split(&mut buffer);
+        buffer.extend_from_slice(&payload);
+
+        let range = write.pack(&mut buffer, envelope, false);
+        let packet = &mut buffer[range];
+
+        assert_eq!(packet.len(), 64 + 4 + payload.len());
+
+        // Server side: the keys are swapped.
+        let init: [u8; 64] = packet[..64].try_into().unwrap();
+
+        let mut reversed: [u8; 48] = init[8..56].try_into().unwrap();
+        reversed.reverse();
+
+        let mut decrypt = AesCtr::new(
+            init[8..40].try_into().unwrap(),
+            init[40..56].try_into().unwrap(),
+        );
+        let mut encrypt = AesCtr::new(
+            reversed[0..32].try_into().unwrap(),
+            reversed[32..48].try_into().unwrap(),
+        );
+
+        decrypt.apply(packet);
+
+        assert_eq!(packet[56..60], Intermediate::INIT);
+        assert_eq!(packet[64..68], 32u32.to_le_bytes());
+        assert_eq!(packet[68..], payload);
+
+        let mut response = [0u8; 4 + 32];
+        response[0..4].copy_from_slice(&32u32.to_le_bytes());
+        response[4..].copy_from_slice(&payload);
+
+        encrypt.apply(&mut response);
+
+        // Feed the response in parts, as it would be read.
+        let mut end = ObfuscatedRead::<Intermediate>::DEFAULT_BUF_LEN;
+
+        let data = loop {
+            match read.unpack(&mut response[..end]) {
+                ControlFlow::Continue(len) => end = len,
+                ControlFlow::Break(Ok(Unpack::Packet(Packet { data }))) => break data,
+                ControlFlow::Break(unpack) => panic!("unexpected unpack: {unpack:?}"),
+            }
+        };
+
+        assert_eq!(response[data], payload);
+    }
+}
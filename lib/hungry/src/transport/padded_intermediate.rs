@@ -0,0 +1,183 @@
+use std::ops::{ControlFlow, RangeFrom};
+
+use bytes::BytesMut;
+
+use crate::mtproto::{EncryptedMessage, PlainMessage};
+use crate::transport::intermediate::{pack_len, status, unpack_len};
+use crate::transport::{
+    Packet, Tagged, Transport, TransportError, TransportRead, TransportWrite, Unpack,
+};
+use crate::{Envelope, EnvelopeSize};
+
+/// Same as [`Intermediate`], but each packet is followed
+/// by 0..15 random bytes included in the length prefix.
+/// Required by obfuscation with proxies using `dd` secrets.
+///
+/// ---
+/// https://core.telegram.org/mtproto/mtproto-transports#padded-intermediate
+///
+/// [`Intermediate`]: crate::transport::Intermediate
+#[derive(Default)]
+pub struct PaddedIntermediate;
+
+pub struct PaddedIntermediateRead;
+
+pub struct PaddedIntermediateWrite {
+    init: bool,
+}
+
+impl PaddedIntermediate {
+    /// The first 4 bytes sent by the client to select the protocol.
+    pub const INIT: [u8; 4] = [0xdd; 4];
+}
+
+impl Transport for PaddedIntermediate {
+    type Read = PaddedIntermediateRead;
+    type Write = PaddedIntermediateWrite;
+
+    fn split(self) -> (Self::Read, Self::Write) {
+        (
+            PaddedIntermediateRead,
+            PaddedIntermediateWrite { init: true },
+        )
+    }
+}
+
+impl Tagged for PaddedIntermediate {
+    const TAG: [u8; 4] = Self::INIT;
+
+    fn split_without_init(self) -> (Self::Read, Self::Write) {
+        (
+            PaddedIntermediateRead,
+            PaddedIntermediateWrite { init: false },
+        )
+    }
+}
+
+impl EnvelopeSize for PaddedIntermediate {
+    const HEADER: usize = 8;
+    const FOOTER: usize = 16; // padding (0..15)
+}
+
+/// Length of the message without the random padding.
+///
+/// The padding is not distinguishable from the payload by the transport
+/// itself, so the message header is used: plain messages contain the
+/// length of their data, encrypted data is always divisible by 16 bytes.
+fn unpadded_len(data: &[u8]) -> Option<usize> {
+    if data.len() < PlainMessage::HEADER_LEN {
+        return None;
+    }
+
+    if data[0..8] == [0; 8] {
+        let len = i32::from_le_bytes(data[16..20].try_into().unwrap());
+
+        return usize::try_from(len)
+            .ok()
+            .map(|len| PlainMessage::HEADER_LEN + len)
+            .filter(|&len| len <= data.len());
+    }
+
+    let len = data.len().checked_sub(EncryptedMessage::HEADER_LEN)?;
+
+    Some(EncryptedMessage::HEADER_LEN + (len & !15))
+}
+
+impl TransportRead for PaddedIntermediateRead {
+    type Transport = PaddedIntermediate;
+
+    const DEFAULT_BUF_LEN: usize = 4;
+
+    fn unpack(&mut self, buffer: &mut [u8]) -> ControlFlow<Result<Unpack, TransportError>, usize> {
+        let data = match unpack_len(buffer) {
+            ControlFlow::Break(Ok(Ok(data))) => data,
+            ControlFlow::Break(Ok(Err(quick_ack))) => {
+                return ControlFlow::Break(Ok(Unpack::QuickAck(quick_ack)));
+            }
+            ControlFlow::Break(Err(err)) => return ControlFlow::Break(Err(err)),
+            ControlFlow::Continue(len) => return ControlFlow::Continue(len),
+        };
+
+        let Some(len) = unpadded_len(&buffer[data.clone()]) else {
+            return ControlFlow::Break(Err(match status(&buffer[data.clone()]) {
+                Some(code) => TransportError::Status(code.into()),
+                None => TransportError::BadLen(data.len() as i32),
+            }));
+        };
+
+        let data = data.start..data.start + len;
+
+        ControlFlow::Break(Ok(Unpack::Packet(Packet { data })))
+    }
+}
+
+impl TransportWrite for PaddedIntermediateWrite {
+    type Transport = PaddedIntermediate;
+
+    fn pack(
+        &mut self,
+        buffer: &mut BytesMut,
+        mut envelope: Envelope<Self::Transport>,
+        quick_ack: bool,
+    ) -> RangeFrom<usize> {
+        let excess = envelope.adapt(buffer);
+        let (h, f) = envelope.buffers();
+
+        let mut padding_len = [0];
+        getrandom::fill(&mut padding_len).unwrap();
+        let padding_len = (padding_len[0] & 15) as usize;
+
+        getrandom::fill(&mut f[..padding_len]).unwrap();
+
+        let len = buffer.len() + padding_len;
+
+        let start = pack_len(h, len, quick_ack, &mut self.init, PaddedIntermediate::INIT);
+
+        envelope.unsplit(buffer, excess);
+
+        buffer.truncate(PaddedIntermediate::HEADER + len);
+
+        start..
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_padded_intermediate() {
+        let (mut read, mut write) = PaddedIntermediate.split();
+
+        let mut payload = [7u8; 24 + 32];
+        payload[..8].copy_from_slice(&1i64.to_le_bytes());
+
+        for init in [true, false] {
+            let mut buffer = BytesMut::with_capacity(64 + payload.len());
+
+            let envelope = Envelope::split(&mut buffer);
+            buffer.extend_from_slice(&payload);
+
+            let range = write.pack(&mut buffer, envelope, false);
+            let packet = &mut buffer[range];
+
+            let packet = if init {
+                assert_eq!(packet[..4], PaddedIntermediate::INIT);
+                &mut packet[4..]
+            } else {
+                packet
+            };
+
+            let len = u32::from_le_bytes(packet[..4].try_into().unwrap()) as usize;
+            assert!((payload.len()..payload.len() + 16).contains(&len));
+            assert_eq!(packet.len(), 4 + len);
+
+            let ControlFlow::Break(Ok(Unpack::Packet(Packet { data }))) = read.unpack(packet)
+            else {
+                panic!("unexpected unpack");
+            };
+
+            assert_eq!(packet[data], payload);
+        }
+    }
+}
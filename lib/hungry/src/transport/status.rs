@@ -0,0 +1,59 @@
+use std::fmt;
+
+/// Error code sent by the server as a negative 4-byte packet.
+///
+/// ---
+/// https://core.telegram.org/mtproto/mtproto-transports#transport-errors
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TransportStatus {
+    /// `-404`: the auth key is not found; it has to be generated again.
+    AuthKeyNotFound,
+    /// `-429`: too many connections from the same IP,
+    /// or a container or service message limit is reached.
+    TransportFlood,
+    /// `-444`: invalid DC id was specified while creating a connection.
+    InvalidDc,
+    /// Any other code, without the sign.
+    Other(i32),
+}
+
+impl TransportStatus {
+    /// The code without the sign.
+    pub fn code(self) -> i32 {
+        use TransportStatus::*;
+
+        match self {
+            AuthKeyNotFound => 404,
+            TransportFlood => 429,
+            InvalidDc => 444,
+            Other(code) => code,
+        }
+    }
+}
+
+impl From<i32> for TransportStatus {
+    /// Convert the code without the sign.
+    fn from(code: i32) -> Self {
+        use TransportStatus::*;
+
+        match code {
+            404 => AuthKeyNotFound,
+            429 => TransportFlood,
+            444 => InvalidDc,
+            code => Other(code),
+        }
+    }
+}
+
+impl fmt::Display for TransportStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TransportStatus::*;
+
+        match self {
+            AuthKeyNotFound => f.write_str("auth key not found (404)"),
+            TransportFlood => f.write_str("transport flood (429)"),
+            InvalidDc => f.write_str("invalid dc (444)"),
+            Other(code) => write!(f, "{code}"),
+        }
+    }
+}
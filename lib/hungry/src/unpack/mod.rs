@@ -1 +1,3 @@
 mod container;
+
+pub use container::MsgContainer;
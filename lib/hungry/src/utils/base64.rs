@@ -0,0 +1,79 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes using the standard alphabet with padding.
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let mut buf = [0; 3];
+        buf[..chunk.len()].copy_from_slice(chunk);
+
+        let n = u32::from_be_bytes([0, buf[0], buf[1], buf[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Decode bytes encoded using either the standard or the URL-safe alphabet,
+/// with or without padding.
+pub(crate) fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+
+    if encoded.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+
+    for chunk in encoded.chunks(4) {
+        let mut n = 0;
+
+        for (i, &x) in chunk.iter().enumerate() {
+            let value = match x {
+                b'A'..=b'Z' => x - b'A',
+                b'a'..=b'z' => x - b'a' + 26,
+                b'0'..=b'9' => x - b'0' + 52,
+                b'+' | b'-' => 62,
+                b'/' | b'_' => 63,
+                _ => return None,
+            };
+
+            n |= (value as u32) << (18 - 6 * i);
+        }
+
+        decoded.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utils_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_utils_base64_decode() {
+        assert_eq!(base64_decode("Zg==").unwrap(), b"f");
+        assert_eq!(base64_decode("Zm8").unwrap(), b"fo");
+        assert_eq!(base64_decode("Zm9vYmFy").unwrap(), b"foobar");
+        assert_eq!(base64_decode("-_8").unwrap(), [0xfb, 0xff]);
+        assert_eq!(base64_decode("Z"), None);
+    }
+}
@@ -1,5 +1,7 @@
+mod base64;
 mod bytes_ext;
 
+pub(crate) use base64::{base64_decode, base64_encode};
 pub(crate) use bytes_ext::{BytesMutExt, unsplit_checked};
 
 macro_rules! ready_ok {
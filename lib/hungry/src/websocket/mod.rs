@@ -0,0 +1,299 @@
+mod read;
+mod write;
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::crypto::sha1;
+use crate::reader::ReaderDriver;
+use crate::utils::base64_encode;
+use crate::writer::WriterDriver;
+
+pub use read::WebSocketRead;
+pub use write::WebSocketWrite;
+
+/// Appended to the key to compute `Sec-WebSocket-Accept`.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC11B65";
+
+/// Pings received by the read half, to be answered by the write half.
+#[derive(Default)]
+struct PongQueue {
+    payloads: VecDeque<Vec<u8>>,
+    /// Wakes the task last polling the write half to send the pongs.
+    waker: Option<Waker>,
+}
+
+type Pongs = Arc<Mutex<PongQueue>>;
+
+/// Longest handshake response header accepted.
+const MAX_RESPONSE_HEADER_LEN: usize = 8192;
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Perform the client handshake, returning drivers which send and receive
+/// binary frames. Any transport can then be used on top of them, usually
+/// an [`Obfuscated`] one, as done by the web clients.
+///
+/// The response is read byte by byte to not consume any frames.
+///
+/// ---
+/// https://datatracker.ietf.org/doc/html/rfc6455#section-4.1
+///
+/// [`Obfuscated`]: crate::transport::Obfuscated
+pub async fn connect<R: ReaderDriver, W: WriterDriver>(
+    mut reader: R,
+    mut writer: W,
+    host: &str,
+    path: &str,
+) -> io::Result<(WebSocketRead<R>, WebSocketWrite<W>)> {
+    let mut key = [0; 16];
+    getrandom::fill(&mut key).unwrap();
+
+    let key = base64_encode(&key);
+
+    let request = format!(
+        "GET {path} HTTP/1.1\r\n\
+        Host: {host}\r\n\
+        Upgrade: websocket\r\n\
+        Connection: Upgrade\r\n\
+        Sec-WebSocket-Key: {key}\r\n\
+        Sec-WebSocket-Version: 13\r\n\
+        Sec-WebSocket-Protocol: binary\r\n\
+        \r\n"
+    );
+
+    writer.write_all(request.as_bytes()).await?;
+    writer.flush().await?;
+
+    let mut response = Vec::new();
+
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() > MAX_RESPONSE_HEADER_LEN {
+            return Err(invalid_data("websocket handshake response is too long"));
+        }
+
+        response.push(reader.read_u8().await?);
+    }
+
+    let response = str::from_utf8(&response)
+        .map_err(|_| invalid_data("websocket handshake response is not valid utf-8"))?;
+
+    let mut lines = response.split("\r\n");
+
+    let status = lines.next().and_then(|line| line.split(' ').nth(1));
+
+    if status != Some("101") {
+        return Err(invalid_data("websocket handshake was rejected"));
+    }
+
+    let accept = lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+
+        name.trim()
+            .eq_ignore_ascii_case("sec-websocket-accept")
+            .then(|| value.trim())
+    });
+
+    let expected = base64_encode(&sha1!(key.as_bytes(), GUID.as_bytes()));
+
+    if accept != Some(expected.as_str()) {
+        return Err(invalid_data("websocket handshake accept key mismatch"));
+    }
+
+    let pongs = Pongs::default();
+
+    Ok((
+        WebSocketRead::new(reader, pongs.clone()),
+        WebSocketWrite::new(writer, pongs),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::poll_fn;
+    use std::pin::Pin;
+    use std::task::{Poll, ready};
+
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Read a masked client frame, as the server would.
+    async fn read_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut header = [0; 2];
+        stream.read_exact(&mut header).await.unwrap();
+
+        assert_eq!(header[1] & 0x80, 0x80);
+
+        let len = match header[1] & 0x7f {
+            126 => stream.read_u16().await.unwrap() as usize,
+            127 => stream.read_u64().await.unwrap() as usize,
+            len => len as usize,
+        };
+
+        let mut mask = [0; 4];
+        stream.read_exact(&mut mask).await.unwrap();
+
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).await.unwrap();
+
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        (header[0], payload)
+    }
+
+    fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![opcode];
+
+        match payload.len() {
+            len @ ..126 => frame.push(len as u8),
+            len @ 126..=0xffff => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// Accept the connection and complete the handshake, as the server would.
+    async fn accept(listener: TcpListener) -> TcpStream {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let mut request = Vec::new();
+
+        while !request.ends_with(b"\r\n\r\n") {
+            request.push(stream.read_u8().await.unwrap());
+        }
+
+        let request = String::from_utf8(request).unwrap();
+
+        assert!(request.starts_with("GET /apiws HTTP/1.1\r\n"));
+        assert!(request.contains("Sec-WebSocket-Protocol: binary\r\n"));
+
+        let key = request
+            .lines()
+            .find_map(|line| line.strip_prefix("Sec-WebSocket-Key: "))
+            .unwrap();
+
+        let accept = base64_encode(&sha1!(key.as_bytes(), GUID.as_bytes()));
+
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Accept: {accept}\r\n\
+            \r\n"
+        );
+
+        stream.write_all(response.as_bytes()).await.unwrap();
+
+        stream
+    }
+
+    #[tokio::test]
+    async fn test_websocket_echo() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut stream = accept(listener).await;
+            for i in 0..2 {
+                // The ping is answered before the next frame.
+                if i > 0 {
+                    assert_eq!(read_frame(&mut stream).await, (0x8a, b"ping".to_vec()));
+                }
+
+                let (opcode, payload) = read_frame(&mut stream).await;
+                assert_eq!(opcode, 0x82);
+
+                // Echo fragmented, with a ping in between.
+                let (a, b) = payload.split_at(payload.len() / 2);
+
+                stream.write_all(&frame(0x02, a)).await.unwrap();
+                stream.write_all(&frame(0x89, b"ping")).await.unwrap();
+                stream.write_all(&frame(0x80, b)).await.unwrap();
+            }
+
+            stream.write_all(&frame(0x88, &[])).await.unwrap();
+        });
+
+        let (r, w) = TcpStream::connect(addr).await.unwrap().into_split();
+
+        let (mut r, mut w) = connect(r, w, &addr.to_string(), "/apiws").await.unwrap();
+
+        for len in [20, 70000] {
+            let payload = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+
+            w.write_all(&payload).await.unwrap();
+            w.flush().await.unwrap();
+
+            let mut echo = vec![0; len];
+            r.read_exact(&mut echo).await.unwrap();
+
+            assert_eq!(echo, payload);
+        }
+
+        assert_eq!(
+            r.read_u8().await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_websocket_ping() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut stream = accept(listener).await;
+
+            // The client answers without writing anything else.
+            stream.write_all(&frame(0x89, b"ping")).await.unwrap();
+            assert_eq!(read_frame(&mut stream).await, (0x8a, b"ping".to_vec()));
+
+            stream.write_all(&frame(0x82, b"data")).await.unwrap();
+        });
+
+        let (r, w) = TcpStream::connect(addr).await.unwrap().into_split();
+
+        let (mut r, mut w) = connect(r, w, &addr.to_string(), "/apiws").await.unwrap();
+
+        let mut data = [0; 4];
+        let mut buf = ReadBuf::new(&mut data);
+
+        // Polled like the sender polls an idle writer.
+        poll_fn(|cx| {
+            if let Poll::Ready(Err(err)) = Pin::new(&mut w).poll_flush(cx) {
+                panic!("{err}");
+            }
+
+            while buf.remaining() > 0 {
+                ready!(Pin::new(&mut r).poll_read(cx, &mut buf)).unwrap();
+            }
+
+            Poll::Ready(())
+        })
+        .await;
+
+        assert_eq!(&data, b"data");
+
+        server.await.unwrap();
+    }
+}
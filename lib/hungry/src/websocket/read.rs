@@ -0,0 +1,215 @@
+use std::io;
+use std::pin::{Pin, pin};
+use std::task::{Context, Poll, ready};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::reader::ReaderDriver;
+use crate::websocket::Pongs;
+
+/// Unwraps the payload of binary frames received from the server.
+///
+/// Pings are answered by the write half, which is woken up to send the pongs,
+/// pongs are discarded, and a close frame is reported as the end of the stream.
+pub struct WebSocketRead<R: ReaderDriver> {
+    driver: R,
+    header: [u8; 14],
+    header_pos: usize,
+    /// Payload bytes left in the current frame.
+    remaining: u64,
+    mask: Option<[u8; 4]>,
+    mask_pos: usize,
+    /// Whether the payload of the current frame is discarded.
+    skip: bool,
+    /// Payload of the current ping frame.
+    ping: Option<Vec<u8>>,
+    pongs: Pongs,
+    closed: bool,
+}
+
+impl<R: ReaderDriver> WebSocketRead<R> {
+    pub(super) fn new(driver: R, pongs: Pongs) -> Self {
+        Self {
+            driver,
+            header: [0; 14],
+            header_pos: 0,
+            remaining: 0,
+            mask: None,
+            mask_pos: 0,
+            skip: false,
+            ping: None,
+            pongs,
+            closed: false,
+        }
+    }
+
+    pub fn driver(&mut self) -> &mut R {
+        &mut self.driver
+    }
+
+    /// Length of the current frame header, as known from the bytes read so far.
+    fn header_len(&self) -> usize {
+        if self.header_pos < 2 {
+            return 2;
+        }
+
+        let len = match self.header[1] & 0x7f {
+            126 => 2,
+            127 => 8,
+            _ => 0,
+        };
+
+        let mask = if self.header[1] & 0x80 != 0 { 4 } else { 0 };
+
+        2 + len + mask
+    }
+
+    fn start_frame(&mut self) -> io::Result<()> {
+        let (remaining, mask) = match self.header[1] & 0x7f {
+            126 => (
+                u16::from_be_bytes([self.header[2], self.header[3]]) as u64,
+                4,
+            ),
+            127 => (
+                u64::from_be_bytes(self.header[2..10].try_into().unwrap()),
+                10,
+            ),
+            len => (len as u64, 2),
+        };
+
+        self.remaining = remaining;
+        self.mask =
+            (self.header[1] & 0x80 != 0).then(|| self.header[mask..mask + 4].try_into().unwrap());
+        self.mask_pos = 0;
+
+        match self.header[0] & 0x0f {
+            // Continuation and binary frames.
+            0x0 | 0x2 => self.skip = false,
+            // Close frame.
+            0x8 => self.closed = true,
+            // Ping frame, answered with the same payload.
+            0x9 => {
+                self.skip = true;
+                self.ping = Some(Vec::new());
+            }
+            // Pong frame.
+            0xa => self.skip = true,
+            opcode => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected websocket opcode: {opcode:#x}"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn unmask(&mut self, buf: &mut [u8]) {
+        let Some(mask) = self.mask else {
+            return;
+        };
+
+        for byte in buf {
+            *byte ^= mask[self.mask_pos % 4];
+
+            self.mask_pos += 1;
+        }
+    }
+}
+
+impl<R: ReaderDriver> AsyncRead for WebSocketRead<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.closed || buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            let len = this.header_len();
+
+            if this.header_pos < len {
+                let mut header = ReadBuf::new(&mut this.header[this.header_pos..len]);
+
+                ready!(pin!(&mut this.driver).poll_read(cx, &mut header))?;
+
+                let n = header.filled().len();
+
+                if n == 0 {
+                    return Poll::Ready(Ok(()));
+                }
+
+                this.header_pos += n;
+
+                if this.header_pos == this.header_len() {
+                    this.start_frame()?;
+                }
+
+                continue;
+            }
+
+            if this.remaining == 0 {
+                this.header_pos = 0;
+
+                if let Some(ping) = this.ping.take() {
+                    let mut pongs = this.pongs.lock().unwrap();
+
+                    pongs.payloads.push_back(ping);
+
+                    if let Some(waker) = pongs.waker.take() {
+                        waker.wake();
+                    }
+                }
+
+                continue;
+            }
+
+            if this.skip {
+                let mut discard = [0; 128];
+                let len = this.remaining.min(discard.len() as u64) as usize;
+
+                let mut discard = ReadBuf::new(&mut discard[..len]);
+
+                ready!(pin!(&mut this.driver).poll_read(cx, &mut discard))?;
+
+                let n = discard.filled().len();
+
+                if n == 0 {
+                    return Poll::Ready(Ok(()));
+                }
+
+                this.remaining -= n as u64;
+                this.unmask(discard.filled_mut());
+
+                if let Some(ping) = &mut this.ping {
+                    ping.extend_from_slice(discard.filled());
+                }
+
+                continue;
+            }
+
+            let len = this.remaining.min(buf.remaining() as u64) as usize;
+
+            let mut payload = buf.take(len);
+
+            ready!(pin!(&mut this.driver).poll_read(cx, &mut payload))?;
+
+            let n = payload.filled().len();
+
+            this.unmask(payload.filled_mut());
+
+            // SAFETY: `n` bytes were initialized by the driver.
+            unsafe { buf.assume_init(n) };
+            buf.advance(n);
+
+            this.remaining -= n as u64;
+
+            return Poll::Ready(Ok(()));
+        }
+    }
+}
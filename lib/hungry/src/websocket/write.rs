@@ -0,0 +1,157 @@
+use std::io;
+use std::pin::{Pin, pin};
+use std::task::{Context, Poll};
+
+use tokio::io::AsyncWrite;
+
+use crate::utils::ready_ok;
+use crate::websocket::Pongs;
+use crate::writer::WriterDriver;
+
+/// Binary frame opcode with the FIN bit.
+const BINARY: u8 = 0x82;
+/// Pong frame opcode with the FIN bit.
+const PONG: u8 = 0x8a;
+
+/// Wraps each write into a masked binary frame sent to the server.
+///
+/// The frame is buffered, and written out with the next write or flush along
+/// with the pongs queued by the read half.
+pub struct WebSocketWrite<W: WriterDriver> {
+    driver: W,
+    frame: Vec<u8>,
+    pos: usize,
+    pongs: Pongs,
+}
+
+impl<W: WriterDriver> WebSocketWrite<W> {
+    pub(super) fn new(driver: W, pongs: Pongs) -> Self {
+        Self {
+            driver,
+            frame: Vec::new(),
+            pos: 0,
+            pongs,
+        }
+    }
+
+    pub fn driver(&mut self) -> &mut W {
+        &mut self.driver
+    }
+
+    fn frame(&mut self, opcode: u8, payload: &[u8]) {
+        let len = payload.len();
+
+        self.frame.push(opcode);
+
+        match len {
+            ..126 => self.frame.push(0x80 | len as u8),
+            126..=0xffff => {
+                self.frame.push(0x80 | 126);
+                self.frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                self.frame.push(0x80 | 127);
+                self.frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+
+        let mut mask = [0; 4];
+        getrandom::fill(&mut mask).unwrap();
+
+        self.frame.extend_from_slice(&mask);
+
+        let start = self.frame.len();
+
+        self.frame.extend_from_slice(payload);
+
+        for (i, byte) in self.frame[start..].iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        self.pos = 0;
+    }
+
+    /// Write the pending frame, then the queued pongs.
+    fn poll_pongs(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            ready_ok!(self.poll_frame(cx));
+
+            let mut pongs = self.pongs.lock().unwrap();
+
+            let Some(payload) = pongs.payloads.pop_front() else {
+                // Woken up by the read half once a ping is received.
+                match &mut pongs.waker {
+                    Some(waker) => waker.clone_from(cx.waker()),
+                    None => pongs.waker = Some(cx.waker().clone()),
+                }
+
+                return Poll::Ready(Ok(()));
+            };
+
+            drop(pongs);
+
+            self.frame(PONG, &payload);
+        }
+    }
+
+    fn poll_frame(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.pos < self.frame.len() {
+            let n = ready_ok!(pin!(&mut self.driver).poll_write(cx, &self.frame[self.pos..]));
+
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "wrote 0 bytes",
+                )));
+            }
+
+            self.pos += n;
+        }
+
+        self.frame.clear();
+        self.pos = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: WriterDriver> AsyncWrite for WebSocketWrite<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        ready_ok!(this.poll_pongs(cx));
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        this.frame(BINARY, buf);
+
+        // The rest of the frame is written with the next write or flush.
+        if let Poll::Ready(Err(err)) = this.poll_frame(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready_ok!(this.poll_pongs(cx));
+
+        pin!(&mut this.driver).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready_ok!(this.poll_pongs(cx));
+
+        pin!(&mut this.driver).poll_shutdown(cx)
+    }
+}
@@ -1,7 +1,7 @@
 mod error;
 mod queued;
 
-use std::io;
+use std::io::{self, IoSlice};
 use std::num::NonZeroUsize;
 use std::pin::pin;
 use std::task::{Context, Poll};
@@ -36,23 +36,34 @@ impl<W: WriterDriver, T: Transport> Writer<W, T> {
     fn poll_checked(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<NonZeroUsize>> {
         let n = ready_ok!(pin!(&mut self.driver).poll_write(cx, buf));
 
+        Poll::Ready(Self::check(n, buf.len()))
+    }
+
+    fn poll_checked_vectored(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<NonZeroUsize>> {
+        let n = ready_ok!(pin!(&mut self.driver).poll_write_vectored(cx, bufs));
+
+        Poll::Ready(Self::check(n, bufs.iter().map(|buf| buf.len()).sum()))
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        pin!(&mut self.driver).poll_flush(cx)
+    }
+
+    fn check(n: usize, len: usize) -> io::Result<NonZeroUsize> {
         assert!(
-            n <= buf.len(),
+            n <= len,
             "`tokio::io::AsyncWrite` contract violation by `{}`: \
             reported number of bytes written ({n}) \
-            exceeds the buffer length ({})",
+            exceeds the buffer length ({len})",
             std::any::type_name::<W>(),
-            buf.len(),
         );
 
-        let Some(n) = NonZeroUsize::new(n) else {
-            return Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::WriteZero,
-                "wrote 0 bytes",
-            )));
-        };
-
-        Poll::Ready(Ok(n))
+        NonZeroUsize::new(n)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::WriteZero, "wrote 0 bytes"))
     }
 
     pub fn single_plain<'a>(
@@ -64,9 +75,10 @@ impl<W: WriterDriver, T: Transport> Writer<W, T> {
     ) -> Single<'a, W, T> {
         mtproto::pack_plain(mtp, buffer, message_id);
 
-        self.single_impl(buffer, transport)
+        self.single_impl(buffer, transport, None)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn single<'a>(
         &'a mut self,
         transport: Envelope<T>,
@@ -75,23 +87,30 @@ impl<W: WriterDriver, T: Transport> Writer<W, T> {
         auth_key: &mtproto::AuthKey,
         message: mtproto::DecryptedMessage,
         msg: mtproto::Msg,
+        quick_ack: bool,
     ) -> Single<'a, W, T> {
-        mtproto::pack_encrypted(mtp, buffer, auth_key, message, msg);
+        let token = mtproto::pack_encrypted(mtp, buffer, auth_key, message, msg);
 
-        self.single_impl(buffer, transport)
+        self.single_impl(
+            buffer,
+            transport,
+            (quick_ack && T::QUICK_ACK).then_some(token),
+        )
     }
 
     fn single_impl<'a>(
         &'a mut self,
         buffer: &'a mut BytesMut,
         transport: Envelope<T>,
+        quick_ack: Option<u32>,
     ) -> Single<'a, W, T> {
-        let range = self.transport.pack(buffer, transport);
+        let range = self.transport.pack(buffer, transport, quick_ack.is_some());
 
         Single {
             writer: self,
             buffer,
             pos: range.start,
+            quick_ack,
         }
     }
 }
@@ -100,6 +119,7 @@ pub struct Single<'a, W: WriterDriver, T: Transport> {
     writer: &'a mut Writer<W, T>,
     buffer: &'a mut BytesMut,
     pos: usize,
+    quick_ack: Option<u32>,
 }
 
 impl<'a, W: WriterDriver, T: Transport> Single<'a, W, T> {
@@ -108,11 +128,20 @@ impl<'a, W: WriterDriver, T: Transport> Single<'a, W, T> {
         self.pos
     }
 
+    /// Expected quick ack token, if it was requested.
+    #[inline]
+    pub fn quick_ack(&self) -> Option<u32> {
+        self.quick_ack
+    }
+
     pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), WriterError>> {
         loop {
             let buf = &self.buffer[self.pos..];
 
             if buf.is_empty() {
+                // The driver may buffer the data.
+                ready_ok!(self.writer.poll_flush(cx));
+
                 return Poll::Ready(Ok(()));
             }
 
@@ -1,5 +1,6 @@
 use std::collections::VecDeque;
-use std::io;
+use std::io::{self, IoSlice};
+use std::mem;
 use std::task::{Context, Poll};
 
 use bytes::BytesMut;
@@ -10,10 +11,15 @@ use crate::utils::BytesMutExt;
 use crate::writer::{Writer, WriterError};
 use crate::{Envelope, mtproto};
 
+/// Largest number of buffers written at once.
+const MAX_IO_SLICES: usize = 64;
+
 pub struct QueuedWriter<W: AsyncWrite + Unpin, T: Transport> {
     error: Option<io::Error>,
     driver: Writer<W, T>,
     buffers: VecDeque<BytesMut>,
+    /// Number of bytes written from the front of the queue.
+    pos: usize,
 }
 
 impl<W: AsyncWrite + Unpin, T: Transport> QueuedWriter<W, T> {
@@ -23,6 +29,7 @@ impl<W: AsyncWrite + Unpin, T: Transport> QueuedWriter<W, T> {
             error: None,
             driver,
             buffers: VecDeque::new(),
+            pos: 0,
         }
     }
 
@@ -37,8 +44,9 @@ impl<W: AsyncWrite + Unpin, T: Transport> QueuedWriter<W, T> {
         &mut self,
         mut buffer: BytesMut,
         envelope: Envelope<T>,
+        quick_ack: bool,
     ) -> (Option<BytesMut>, Option<BytesMut>) {
-        let packed = self.driver.transport.pack(&mut buffer, envelope);
+        let packed = self.driver.transport.pack(&mut buffer, envelope, quick_ack);
 
         let header = if packed.start > 0 {
             Some(buffer.split_to(packed.start))
@@ -73,9 +81,11 @@ impl<W: AsyncWrite + Unpin, T: Transport> QueuedWriter<W, T> {
     ) -> (Option<BytesMut>, Option<BytesMut>) {
         mtproto::pack_plain(mtp, &mut buffer, message_id);
 
-        self.queue_impl(buffer, transport)
+        self.queue_impl(buffer, transport, false)
     }
 
+    /// The expected quick ack token is returned if it was requested.
+    #[allow(clippy::too_many_arguments)]
     #[must_use = "the `BytesMut` must be reused to avoid unnecessary memory reallocation"]
     pub fn queue(
         &mut self,
@@ -85,52 +95,197 @@ impl<W: AsyncWrite + Unpin, T: Transport> QueuedWriter<W, T> {
         auth_key: &mtproto::AuthKey,
         message: mtproto::DecryptedMessage,
         msg: mtproto::Msg,
-    ) -> (Option<BytesMut>, Option<BytesMut>) {
-        mtproto::pack_encrypted(mtp, &mut buffer, auth_key, message, msg);
+        quick_ack: bool,
+    ) -> (Option<BytesMut>, Option<BytesMut>, Option<u32>) {
+        let token = mtproto::pack_encrypted(mtp, &mut buffer, auth_key, message, msg);
+
+        let quick_ack = quick_ack && T::QUICK_ACK;
+
+        let (header, footer) = self.queue_impl(buffer, transport, quick_ack);
 
-        self.queue_impl(buffer, transport)
+        (header, footer, quick_ack.then_some(token))
     }
 
+    /// Write the queued buffers, vectored if supported by the driver.
+    /// A written buffer is returned for reuse; a partially written
+    /// buffer is split, and its written part is returned.
     pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<BytesMut, WriterError>> {
         if let Some(error) = self.error.take() {
             return Poll::Ready(Err(WriterError::Io(error)));
         }
 
-        let Some(buffer) = self.buffers.front_mut() else {
-            return Poll::Pending;
-        };
-
-        let mut pos = 0;
-
         loop {
-            let ready = match self.driver.poll_checked(cx, &buffer[pos..]) {
-                Poll::Ready(ready) => ready,
-                Poll::Pending if pos == 0 => return Poll::Pending,
-                Poll::Pending => return Poll::Ready(Ok(buffer.split_to(pos))),
+            let Some(buffer) = self.buffers.front() else {
+                // The driver may buffer the data, or have data of its own to send.
+                return match self.driver.poll_flush(cx) {
+                    Poll::Ready(Err(err)) => Poll::Ready(Err(WriterError::Io(err))),
+                    _ => Poll::Pending,
+                };
             };
 
-            let n = match ready {
-                Ok(n) => n.get(),
-                Err(err) if pos == 0 => return Poll::Ready(Err(WriterError::Io(err))),
-                Err(err) => {
-                    // Immediately wake the task so the error will be returned.
-                    cx.waker().wake_by_ref();
+            // Buffers may have been written by a single vectored write.
+            if self.pos >= buffer.len() {
+                self.pos -= buffer.len();
 
-                    self.error = Some(err);
+                return Poll::Ready(Ok(self.buffers.pop_front().unwrap()));
+            }
+
+            let ready = if self.buffers.len() > 1 && self.driver.driver.is_write_vectored() {
+                let mut bufs = [IoSlice::new(&[]); MAX_IO_SLICES];
+
+                let mut iter = self.buffers.iter();
+
+                bufs[0] = IoSlice::new(&iter.next().unwrap()[self.pos..]);
 
-                    return Poll::Ready(Ok(buffer.split_to(pos)));
+                let mut len = 1;
+
+                for (buf, buffer) in bufs[1..].iter_mut().zip(iter) {
+                    *buf = IoSlice::new(buffer);
+
+                    len += 1;
                 }
+
+                self.driver.poll_checked_vectored(cx, &bufs[..len])
+            } else {
+                self.driver.poll_checked(cx, &buffer[self.pos..])
+            };
+
+            let ready = match ready {
+                Poll::Ready(ready) => ready,
+                Poll::Pending => return self.split_written().map_or(Poll::Pending, Poll::Ready),
             };
 
-            pos += n;
+            match ready {
+                Ok(n) => self.pos += n.get(),
+                Err(err) => match self.split_written() {
+                    None => return Poll::Ready(Err(WriterError::Io(err))),
+                    Some(written) => {
+                        // Immediately wake the task so the error will be returned.
+                        cx.waker().wake_by_ref();
+
+                        self.error = Some(err);
+
+                        return Poll::Ready(written);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Split the written part of the partially written front buffer.
+    fn split_written(&mut self) -> Option<Result<BytesMut, WriterError>> {
+        if self.pos == 0 {
+            return None;
+        }
+
+        let buffer = self.buffers.front_mut().unwrap();
+
+        let written = buffer.split_to(mem::take(&mut self.pos));
+
+        Some(Ok(written))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::pin::Pin;
+    use std::task::Waker;
 
-            if pos < buffer.len() {
-                continue;
+    use crate::EnvelopeSize;
+    use crate::transport::{Intermediate, Tagged};
+
+    /// Accepts up to `limit` bytes per write.
+    struct Driver {
+        written: Vec<u8>,
+        writes: usize,
+        limit: usize,
+    }
+
+    impl AsyncWrite for Driver {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+        }
+
+        fn poll_write_vectored(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+
+            this.writes += 1;
+
+            let mut n = 0;
+
+            for buf in bufs {
+                let len = buf.len().min(this.limit - n);
+
+                this.written.extend_from_slice(&buf[..len]);
+
+                n += len;
             }
 
-            assert_eq!(pos, buffer.len());
+            Poll::Ready(Ok(n))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            true
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_writer_queued_vectored() {
+        let driver = Driver {
+            written: Vec::new(),
+            writes: 0,
+            limit: 50,
+        };
+
+        let (_, transport) = Intermediate.split_without_init();
+
+        let mut writer = QueuedWriter::<_, Intermediate>::new(Writer::new(driver, transport));
+
+        let mut expected = Vec::new();
+
+        for i in 0..4u8 {
+            let mut buffer = BytesMut::with_capacity(Intermediate::HEADER + 16);
 
-            return Poll::Ready(Ok(self.buffers.pop_front().unwrap()));
+            let envelope = Envelope::split(&mut buffer);
+            buffer.extend_from_slice(&[i; 16]);
+
+            let (header, footer) = writer.queue_impl(buffer, envelope, false);
+            assert!(header.is_some() && footer.is_none());
+
+            expected.extend_from_slice(&16u32.to_le_bytes());
+            expected.extend_from_slice(&[i; 16]);
+        }
+
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut returned = 0;
+
+        while let Poll::Ready(buffer) = writer.poll(&mut cx) {
+            returned += buffer.unwrap().len();
         }
+
+        let driver = writer.driver.driver();
+
+        assert_eq!(driver.written, expected);
+        assert_eq!(driver.writes, 2);
+        assert_eq!(returned, expected.len());
     }
 }